@@ -0,0 +1,101 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use serde::Deserialize;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::error::RetrieveError;
+
+
+/// 解析 `0x` 开头的 20 字节十六进制地址，返回小写形式
+///
+/// 大小写混合的地址按照 EIP-55 校验，全大写或全小写的地址不校验
+pub fn parse(address: &str) -> Result<String, RetrieveError> {
+    let trimmed = address.trim();
+    let invalid = |reason: &str| RetrieveError::InvalidAddress(format!("'{}': {}", trimmed, reason));
+    let hex = trimmed.strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+        .ok_or_else(|| invalid("expected a 0x prefix"))?;
+    if hex.len() != 40 {
+        return Err(invalid("expected 40 hex digits"));
+    }
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid("not a hex string"));
+    }
+    let lower = format!("0x{}", hex.to_ascii_lowercase());
+    let mixed = hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+    if mixed && to_checksum(&lower)[2..] != *hex {
+        return Err(invalid("bad EIP-55 checksum"));
+    }
+    Ok(lower)
+}
+
+
+/// EIP-55 校验和形式，`address` 必须是 [`parse`] 返回的小写地址
+pub fn to_checksum(address: &str) -> String {
+    let hex = &address[2..];
+    let mut hash = [0u8; 32];
+    let mut keccak = Keccak::v256();
+    keccak.update(hex.as_bytes());
+    keccak.finalize(&mut hash);
+    let checksummed: String = hex.chars().enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+            if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+        })
+        .collect();
+    format!("0x{}", checksummed)
+}
+
+
+/// 目录名称中地址的大小写
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressCase {
+    /// EIP-55 校验和形式
+    #[default]
+    Checksum,
+    Lowercase,
+}
+
+impl AddressCase {
+    /// `address` 必须是 [`parse`] 返回的小写地址
+    pub fn format(self, address: &str) -> String {
+        match self {
+            AddressCase::Checksum => to_checksum(address),
+            AddressCase::Lowercase => address.to_string(),
+        }
+    }
+}
+
+impl FromStr for AddressCase {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "checksum" => Ok(AddressCase::Checksum),
+            "lowercase" => Ok(AddressCase::Lowercase),
+            _ => Err(anyhow!("Invalid address case '{}', expected checksum or lowercase", s)),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address() {
+        let usdt = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        assert_eq!(parse(" 0xdAC17F958D2ee523a2206206994597C13D831ec7 ").unwrap(), usdt);
+        assert_eq!(parse("0XDAC17F958D2EE523A2206206994597C13D831EC7").unwrap(), usdt);
+        assert_eq!(to_checksum(usdt), "0xdAC17F958D2ee523a2206206994597C13D831ec7");
+        assert_eq!(AddressCase::Lowercase.format(usdt), usdt);
+
+        for invalid in ["dac17f958d2ee523a2206206994597c13d831ec7", "0xabc", "0xzac17f958d2ee523a2206206994597c13d831ec7",
+                        "0xDAc17F958D2ee523a2206206994597C13D831ec7", ""] {
+            assert!(matches!(parse(invalid), Err(RetrieveError::InvalidAddress(_))), "{}", invalid);
+        }
+    }
+}
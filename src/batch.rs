@@ -0,0 +1,439 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use csv::StringRecord;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde_json::Value;
+
+use crate::address;
+use crate::chain::ChainRegistry;
+use crate::config::ApiKeys;
+use crate::{get_code, OutputOptions};
+use crate::ledger::{JobStatus, Ledger};
+use crate::manifest;
+use crate::report::Outcome;
+
+
+/// 批量模式中的一个地址
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    /// 在输入文件中的行号
+    pub line: u64,
+    pub address: String,
+    pub chain: String,
+}
+
+
+/// 输入文件中地址和链所在的列
+#[derive(Debug, Clone, Default)]
+pub struct InputOptions {
+    /// 表头中的列名或者从 1 开始的列号，默认为 `address` 列或第一列
+    pub address_col: Option<String>,
+    /// 表头中的列名或者从 1 开始的列号，默认为 `chain` 列或第二列
+    pub chain_col: Option<String>,
+    /// 没有链这一列或者为空时使用的链
+    pub default_chain: Option<String>,
+}
+
+
+/// 读取输入文件中的所有地址，`file` 为 `-` 时从标准输入读取
+///
+/// 支持以下格式，根据扩展名判断，没有扩展名时根据内容判断：
+/// - CSV（`.csv`）和 TSV（`.tsv`），表头可选，第一行中有 `address`、`chain` 或者指定的列名时视为表头
+/// - JSONL（`.jsonl`、`.ndjson`），每行一个对象
+/// - 每行一个地址的列表，链使用 `default_chain`
+///
+/// 无法解析的行不影响其他行，作为 `invalid_row` 返回；地址格式错误的行作为 `invalid_address` 返回，
+/// 有效的地址统一为小写
+pub fn read_jobs(file: &str, input: &InputOptions) -> Result<(Vec<Job>, Vec<Outcome>)> {
+    let content = if file == "-" {
+        io::read_to_string(io::stdin()).context("Failed to read addresses from stdin")?
+    } else {
+        fs::read_to_string(file).with_context(|| format!("Failed to read {}", file))?
+    };
+    parse_jobs(file, &content, input)
+}
+
+
+fn parse_jobs(file: &str, content: &str, input: &InputOptions) -> Result<(Vec<Job>, Vec<Outcome>)> {
+    let rows = match InputFormat::detect(file, content) {
+        InputFormat::Jsonl => jsonl_rows(content, input),
+        InputFormat::Delimited(delimiter) => delimited_rows(content, delimiter, input)?,
+    };
+    let mut jobs = Vec::new();
+    let mut invalid = Vec::new();
+    for row in rows {
+        let (line, address, chain) = match row {
+            Ok(row) => row,
+            Err(outcome) => {
+                invalid.push(outcome);
+                continue;
+            }
+        };
+        let chain = match (chain.is_empty(), &input.default_chain) {
+            (true, Some(default)) => default.clone(),
+            _ => chain,
+        };
+        if address.is_empty() || chain.is_empty() {
+            invalid.push(Outcome::invalid_row(line, "Expected an address and a chain (use --chain to set a default chain)"));
+            continue;
+        }
+        match address::parse(&address) {
+            Ok(address) => jobs.push(Job { line, address, chain }),
+            Err(e) => invalid.push(Outcome::new(line, &chain, &address, &Err(e))),
+        }
+    }
+    Ok((jobs, invalid))
+}
+
+
+/// 一行的行号、地址和链，链可能为空
+type Row = Result<(u64, String, String), Outcome>;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InputFormat {
+    Jsonl,
+    /// CSV、TSV 和地址列表，地址列表就是只有一列的 CSV
+    Delimited(u8),
+}
+
+impl InputFormat {
+    fn detect(file: &str, content: &str) -> Self {
+        let extension = Path::new(file).extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("jsonl" | "ndjson") => return InputFormat::Jsonl,
+            Some("tsv") => return InputFormat::Delimited(b'\t'),
+            Some("csv") => return InputFormat::Delimited(b','),
+            _ => {}
+        }
+        let first = content.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with('#'));
+        match first {
+            Some(line) if line.starts_with('{') => InputFormat::Jsonl,
+            Some(line) if line.contains('\t') => InputFormat::Delimited(b'\t'),
+            _ => InputFormat::Delimited(b','),
+        }
+    }
+}
+
+
+fn delimited_rows(content: &str, delimiter: u8, input: &InputOptions) -> Result<Vec<Row>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let mut columns = None;
+    let mut rows = Vec::new();
+    for result in rdr.records() {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map_or(0, |p| p.line());
+                rows.push(Err(Outcome::invalid_row(line, &e.to_string())));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |p| p.line());
+        // `#` 开头的行为注释
+        if record.get(0).is_some_and(|field| field.starts_with('#')) {
+            continue;
+        }
+        let (address_col, chain_col) = match columns {
+            Some(columns) => columns,
+            None => {
+                // 第一行中有 `address`、`chain` 或者指定的列名时为表头，
+                // 否则按位置取列，第一行的地址写错时只影响这一行
+                let names: Vec<&str> = [input.address_col.as_deref(), input.chain_col.as_deref()].into_iter()
+                    .flatten()
+                    .filter(|col| col.parse::<usize>().is_err())
+                    .chain(["address", "chain"])
+                    .collect();
+                let header = record.iter().any(|field| names.iter().any(|name| field.eq_ignore_ascii_case(name)));
+                let found = if header { Some(&record) } else { None };
+                let address_col = column(found, input.address_col.as_deref(), "address")?.unwrap_or(0);
+                let default_chain_col = match (found, address_col) {
+                    (None, 0) => Some(1),
+                    _ => None,
+                };
+                let chain_col = column(found, input.chain_col.as_deref(), "chain")?.or(default_chain_col);
+                columns = Some((address_col, chain_col));
+                if header {
+                    continue;
+                }
+                (address_col, chain_col)
+            }
+        };
+        let field = |col: Option<usize>| col.and_then(|col| record.get(col)).unwrap_or("").to_string();
+        rows.push(Ok((line, field(Some(address_col)), field(chain_col))));
+    }
+    Ok(rows)
+}
+
+
+/// `spec` 为列号或者表头中的列名，没有指定时在表头中查找 `name`
+fn column(header: Option<&StringRecord>, spec: Option<&str>, name: &str) -> Result<Option<usize>> {
+    let find = |name: &str| header.and_then(|h| h.iter().position(|field| field.eq_ignore_ascii_case(name)));
+    match spec {
+        Some(spec) => match spec.parse::<usize>() {
+            Ok(0) => Err(anyhow!("Column numbers start at 1")),
+            Ok(number) => Ok(Some(number - 1)),
+            Err(_) => find(spec).map(Some).ok_or_else(|| anyhow!("Column '{}' not found in the header", spec)),
+        },
+        None => Ok(find(name)),
+    }
+}
+
+
+fn jsonl_rows(content: &str, input: &InputOptions) -> Vec<Row> {
+    let address_key = input.address_col.as_deref().unwrap_or("address");
+    let chain_key = input.chain_col.as_deref().unwrap_or("chain");
+    content.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            let line_number = i as u64 + 1;
+            let value: Value = serde_json::from_str(line)
+                .map_err(|e| Outcome::invalid_row(line_number, &format!("Invalid JSON: {}", e)))?;
+            if !value.is_object() {
+                return Err(Outcome::invalid_row(line_number, "Expected a JSON object"));
+            }
+            // chain 可以是 chain id 数字
+            let field = |key: &str| match &value[key] {
+                Value::String(s) => s.trim().to_string(),
+                Value::Number(n) => n.to_string(),
+                _ => String::new(),
+            };
+            Ok((line_number, field(address_key), field(chain_key)))
+        })
+        .collect()
+}
+
+
+/// 根据之前的处理记录决定哪些地址需要处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// 处理所有地址
+    Off,
+    /// 跳过已经完成或者确认未验证的地址
+    Unfinished,
+    /// 只处理失败的地址
+    FailedOnly,
+}
+
+impl Resume {
+    fn should_run(self, status: Option<JobStatus>) -> bool {
+        match self {
+            Resume::Off => true,
+            Resume::Unfinished => !matches!(status, Some(JobStatus::Done | JobStatus::NotVerified)),
+            Resume::FailedOnly => status == Some(JobStatus::Failed),
+        }
+    }
+}
+
+
+/// 按照 `resume` 过滤掉不需要处理的地址
+pub fn filter_jobs(jobs: Vec<Job>, resume: Resume, ledger: &Ledger, registry: &ChainRegistry) -> Vec<Job> {
+    let total = jobs.len();
+    let jobs: Vec<_> = jobs.into_iter()
+        .filter(|job| resume.should_run(ledger.status(&ledger_chain(registry, &job.chain), &job.address)))
+        .collect();
+    if resume != Resume::Off {
+        println!("Skipping {} of {} addresses recorded in the ledger", total - jobs.len(), total);
+    }
+    jobs
+}
+
+
+/// 去掉重复的地址，同一条链的别名和 chain id 视为同一条链，保留第一次出现的行
+pub fn dedupe_jobs(jobs: Vec<Job>, registry: &ChainRegistry) -> Vec<Job> {
+    let total = jobs.len();
+    let mut seen = HashSet::new();
+    let jobs: Vec<_> = jobs.into_iter()
+        .filter(|job| seen.insert((ledger_chain(registry, &job.chain), job.address.clone())))
+        .collect();
+    if jobs.len() < total {
+        println!("Skipping {} duplicate addresses", total - jobs.len());
+    }
+    jobs
+}
+
+
+/// 跳过输出目录中已经有完整 `manifest.json` 的地址
+pub fn skip_existing(jobs: Vec<Job>, output: &OutputOptions, registry: &ChainRegistry) -> Vec<Job> {
+    let total = jobs.len();
+    let jobs: Vec<_> = jobs.into_iter()
+        .filter(|job| {
+            // 无法识别的链留到处理时报错
+            let Ok(chain) = registry.resolve(&job.chain) else {
+                return true;
+            };
+            let dir = Path::new(&output.basepath).join(&chain.name).join(output.address_case.format(&job.address));
+            !manifest::is_complete(&dir, output.abi_only, output.layout)
+        })
+        .collect();
+    println!("Skipping {} of {} addresses already downloaded", total - jobs.len(), total);
+    jobs
+}
+
+
+/// 最多同时处理 `concurrency` 个地址，请求频率由各个数据源的限流器控制
+///
+/// 单个地址失败不影响其他地址，结果写入 `ledger` 并返回。只有写入 `ledger` 失败时才中止
+pub async fn run(
+    output: &OutputOptions,
+    jobs: Vec<Job>,
+    concurrency: usize,
+    registry: &ChainRegistry,
+    keys: &ApiKeys,
+    ledger: &Ledger,
+) -> Result<Vec<Outcome>> {
+    stream::iter(jobs)
+        .map(|job| async move {
+            let result = get_code(output, &job.address, &job.chain, registry, keys).await;
+            if let Err(e) = &result {
+                println!("Failed to retrieve {} on {}: {}", job.address, job.chain, e);
+            }
+            ledger.record(&ledger_chain(registry, &job.chain), &job.address, &result)?;
+            Ok(Outcome::new(job.line, &job.chain, &job.address, &result))
+        })
+        .buffer_unordered(concurrency)
+        .try_collect()
+        .await
+}
+
+
+/// 记录中使用链名称，同一条链的别名和 chain id 视为同一条链
+fn ledger_chain(registry: &ChainRegistry, chain: &str) -> String {
+    registry.resolve(chain).map_or_else(|_| chain.to_string(), |c| c.name.clone())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::RetrieveError;
+    use std::io::Write;
+
+    #[test]
+    fn test_read_jobs() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "address,chain\n0xdAC17F958D2ee523a2206206994597C13D831ec7, eth\n0x02\n\
+                        0x2791bca1f2de4661ed88a30c99a7a9449aa84174,137\n0x2791,137").unwrap();
+        let (jobs, invalid) = read_jobs(file.path().to_str().unwrap(), &InputOptions::default()).unwrap();
+        assert_eq!(jobs, vec![
+            Job { line: 2, address: "0xdac17f958d2ee523a2206206994597c13d831ec7".to_string(), chain: "eth".to_string() },
+            Job { line: 4, address: "0x2791bca1f2de4661ed88a30c99a7a9449aa84174".to_string(), chain: "137".to_string() },
+        ]);
+        assert_eq!(invalid.len(), 2);
+        assert_eq!((invalid[0].line, invalid[0].status.as_str()), (3, "invalid_row"));
+        assert_eq!((invalid[1].line, invalid[1].status.as_str()), (5, "invalid_address"));
+        assert!(invalid[1].error.as_deref().unwrap().contains("Invalid address: '0x2791'"));
+    }
+
+    #[test]
+    fn test_parse_input_formats() {
+        const A: &str = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+        const B: &str = "0x2791bca1f2de4661ed88a30c99a7a9449aa84174";
+        let parse = |file: &str, content: &str, input: &InputOptions| {
+            let (jobs, invalid) = parse_jobs(file, content, input).unwrap();
+            let jobs: Vec<_> = jobs.into_iter().map(|job| (job.line, job.address, job.chain)).collect();
+            (jobs, invalid.len())
+        };
+        let job = |line: u64, address: &str, chain: &str| (line, address.to_string(), chain.to_string());
+        let default = InputOptions::default();
+
+        // 没有表头的 CSV
+        assert_eq!(parse("in.csv", &format!("{A},eth\n{B},137\n"), &default),
+                   (vec![job(1, A, "eth"), job(2, B, "137")], 0));
+        // 按列名指定列
+        let named = InputOptions { address_col: Some("contract".into()), chain_col: Some("network".into()), default_chain: None };
+        assert_eq!(parse("in.csv", &format!("network,name,contract\neth,USDT,{A}\n"), &named),
+                   (vec![job(2, A, "eth")], 0));
+        assert!(parse_jobs("in.csv", "chain,address\n", &named).is_err());
+        // 按列号指定列
+        let numbered = InputOptions { address_col: Some("2".into()), chain_col: Some("1".into()), default_chain: None };
+        assert_eq!(parse("in.csv", &format!("eth,{A}\n"), &numbered), (vec![job(1, A, "eth")], 0));
+        // TSV，表头只有地址，使用默认链
+        let with_chain = InputOptions { default_chain: Some("eth".into()), ..InputOptions::default() };
+        assert_eq!(parse("in.tsv", &format!("name\taddress\nUSDT\t{A}\n"), &with_chain),
+                   (vec![job(2, A, "eth")], 0));
+        // JSONL，chain 可以是数字
+        let jsonl = format!("{{\"address\": \"{A}\", \"chain\": \"eth\"}}\n\n{{\"address\": \"{B}\", \"chain\": 137}}\nnot json\n[]\n");
+        assert_eq!(parse("-", &jsonl, &default), (vec![job(1, A, "eth"), job(3, B, "137")], 2));
+        // 地址列表
+        assert_eq!(parse("-", &format!("{A}\n# comment\n{B}\n"), &with_chain),
+                   (vec![job(1, A, "eth"), job(3, B, "eth")], 0));
+        assert_eq!(parse("-", &format!("{A}\n"), &default), (vec![], 1));
+        // 第一行的地址无效时不会被当作表头
+        assert_eq!(parse("-", &format!("0x123\n{A}\n"), &with_chain), (vec![job(2, A, "eth")], 1));
+        // 第一行缺少 `0x` 前缀时也不会被当作表头，只有这一行无效
+        let (jobs, invalid) = parse_jobs("in.csv", &format!("{},eth\n{B},137\n", &A[2..]), &default).unwrap();
+        assert_eq!(jobs.into_iter().map(|job| job.line).collect::<Vec<_>>(), [2]);
+        assert_eq!((invalid[0].line, invalid[0].status.as_str()), (1, "invalid_address"));
+    }
+
+    #[test]
+    fn test_filter_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let ledger = Ledger::open(dir.path().to_str().unwrap()).unwrap();
+        ledger.record("eth", "0x01", &Ok(())).unwrap();
+        ledger.record("eth", "0x02", &Err(RetrieveError::Network("timeout".to_string()))).unwrap();
+        ledger.record("pg", "0x03", &Err(RetrieveError::NotVerified("0x03".to_string()))).unwrap();
+        let ledger = Ledger::open(dir.path().to_str().unwrap()).unwrap();
+        let registry = ChainRegistry::load(&crate::config::Config::default()).unwrap();
+
+        let jobs: Vec<_> = [("0x01", "ethereum"), ("0x02", "1"), ("0x03", "polygon"), ("0x04", "eth")].iter()
+            .map(|(address, chain)| Job { line: 0, address: address.to_string(), chain: chain.to_string() })
+            .collect();
+        let addresses = |resume| filter_jobs(jobs.clone(), resume, &ledger, &registry).into_iter()
+            .map(|job| job.address)
+            .collect::<Vec<_>>();
+        assert_eq!(addresses(Resume::Off), ["0x01", "0x02", "0x03", "0x04"]);
+        assert_eq!(addresses(Resume::Unfinished), ["0x02", "0x04"]);
+        assert_eq!(addresses(Resume::FailedOnly), ["0x02"]);
+    }
+
+    fn jobs(rows: &[(u64, &str, &str)]) -> Vec<Job> {
+        rows.iter()
+            .map(|(line, address, chain)| Job { line: *line, address: address.to_string(), chain: chain.to_string() })
+            .collect()
+    }
+
+    #[test]
+    fn test_dedupe_jobs() {
+        let registry = ChainRegistry::load(&crate::config::Config::default()).unwrap();
+        let rows = jobs(&[(1, "0x01", "eth"), (2, "0x02", "eth"), (3, "0x01", "1"), (4, "0x01", "ethereum"), (5, "0x01", "pg")]);
+        let lines: Vec<_> = dedupe_jobs(rows, &registry).into_iter().map(|job| job.line).collect();
+        assert_eq!(lines, [1, 2, 5]);
+    }
+
+    #[test]
+    fn test_skip_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = ChainRegistry::load(&crate::config::Config::default()).unwrap();
+        let output = OutputOptions {
+            basepath: dir.path().to_str().unwrap().to_string(),
+            address_case: crate::address::AddressCase::Lowercase,
+            ..OutputOptions::default()
+        };
+        // 只有清单完整的地址被跳过
+        let contract_dir = dir.path().join("eth").join("0x01");
+        fs::create_dir_all(&contract_dir).unwrap();
+        fs::write(contract_dir.join("abi.json"), "[]").unwrap();
+        fs::write(contract_dir.join(manifest::MANIFEST_FILE), r#"{"files": ["abi.json"], "layout": "flat"}"#).unwrap();
+        fs::create_dir_all(dir.path().join("eth").join("0x02")).unwrap();
+        let rows = jobs(&[(1, "0x01", "ethereum"), (2, "0x02", "eth"), (3, "0x01", "pg"), (4, "0x03", "unknown")]);
+        let remaining: Vec<_> = skip_existing(rows.clone(), &output, &registry).into_iter().map(|job| job.line).collect();
+        assert_eq!(remaining, [2, 3, 4]);
+
+        // 换一种布局时重新下载
+        let foundry = OutputOptions { layout: crate::layout::Layout::Foundry, ..output };
+        assert_eq!(skip_existing(rows, &foundry, &registry).len(), 4);
+    }
+}
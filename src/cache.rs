@@ -0,0 +1,143 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tempfile::NamedTempFile;
+use tiny_keccak::{Hasher, Keccak};
+
+/// 默认缓存一天
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// 缓存时去掉的查询参数
+const SECRET_PARAMS: [&str; 3] = ["apikey", "api_key", "key"];
+
+
+/// 区块浏览器 GET 响应的本地缓存，保存在 `{dir}/{host}/{hash}.json`
+///
+/// 缓存的键为去掉 API key 的请求 URL，其中包含了数据源、链和合约地址，
+/// 因此更换 API key 不影响缓存
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+    /// 只使用缓存，不发出请求，此时过期的缓存仍然有效
+    pub offline: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    key: String,
+    /// Unix 时间戳，单位为秒
+    fetched_at: u64,
+    /// 404 时为 `None`
+    body: Option<Value>,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf, ttl: Duration, offline: bool) -> Self {
+        Cache { dir, ttl, offline }
+    }
+
+    /// 缓存的响应，404 时为 `Some(None)`，没有缓存或者已经过期时为 `None`
+    pub fn get(&self, url: &str) -> Option<Option<Value>> {
+        let key = key(url);
+        let content = fs::read_to_string(self.path(&key)).ok()?;
+        let entry: Entry = serde_json::from_str(&content).ok()?;
+        if entry.key != key {
+            return None;
+        }
+        let age = now().saturating_sub(entry.fetched_at);
+        if !self.offline && age > self.ttl.as_secs() {
+            return None;
+        }
+        Some(entry.body)
+    }
+
+    /// 先写入同一目录下的临时文件再重命名，并发写入同一个键时不会留下不完整的文件
+    pub fn put(&self, url: &str, body: Option<&Value>) -> Result<()> {
+        let key = key(url);
+        let path = self.path(&key);
+        let parent = path.parent().unwrap();
+        fs::create_dir_all(parent)?;
+        let entry = Entry { key, fetched_at: now(), body: body.cloned() };
+        let mut tmp = NamedTempFile::new_in(parent)?;
+        serde_json::to_writer(&mut tmp, &entry)?;
+        tmp.persist(&path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let host = reqwest::Url::parse(key).ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| "unknown".to_string());
+        let mut hash = [0u8; 32];
+        let mut keccak = Keccak::v256();
+        keccak.update(key.as_bytes());
+        keccak.finalize(&mut hash);
+        let name: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(host).join(format!("{}.json", name))
+    }
+}
+
+
+/// 去掉 API key 参数后的 URL
+pub fn key(url: &str) -> String {
+    let Ok(mut parsed) = reqwest::Url::parse(url) else {
+        return url.to_string();
+    };
+    let pairs: Vec<(String, String)> = parsed.query_pairs()
+        .filter(|(name, _)| !SECRET_PARAMS.contains(&name.to_lowercase().as_str()))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    if pairs.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    parsed.to_string()
+}
+
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cache() {
+        assert_eq!(key("https://api.etherscan.io/v2/api?chainid=1&module=contract&address=0x01&apikey=SECRET"),
+                   "https://api.etherscan.io/v2/api?chainid=1&module=contract&address=0x01");
+        assert_eq!(key("https://x.io/api?key=SECRET"), "https://x.io/api");
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path().to_path_buf(), DEFAULT_TTL, false);
+        let url = "https://api.etherscan.io/v2/api?chainid=1&address=0x01&apikey=A";
+        assert_eq!(cache.get(url), None);
+        cache.put(url, Some(&json!({"status": "1"}))).unwrap();
+        // 更换 API key 仍然命中
+        assert_eq!(cache.get("https://api.etherscan.io/v2/api?chainid=1&address=0x01&apikey=B"), Some(Some(json!({"status": "1"}))));
+        assert_eq!(cache.get("https://api.etherscan.io/v2/api?chainid=1&address=0x02&apikey=A"), None);
+        cache.put("https://eth.blockscout.com/api/v2/smart-contracts/0x01", None).unwrap();
+        assert_eq!(cache.get("https://eth.blockscout.com/api/v2/smart-contracts/0x01"), Some(None));
+
+        // 过期的缓存只在离线模式下使用
+        let expired = Cache::new(dir.path().to_path_buf(), Duration::ZERO, false);
+        let path = expired.path(&key(url));
+        let mut entry: Entry = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        entry.fetched_at -= 10;
+        fs::write(&path, serde_json::to_string(&entry).unwrap()).unwrap();
+        assert_eq!(expired.get(url), None);
+        let offline = Cache::new(dir.path().to_path_buf(), Duration::ZERO, true);
+        assert!(offline.get(url).is_some());
+        // 只剩下缓存文件，没有残留的临时文件
+        assert!(fs::read_dir(path.parent().unwrap()).unwrap().all(|e| e.unwrap().path().extension().is_some_and(|ext| ext == "json")));
+    }
+}
@@ -0,0 +1,417 @@
+use std::fmt;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::config::{ChainConfig, Config, ExplorerConfig};
+use crate::error::RetrieveError;
+use crate::provider::SOURCIFY_URL;
+
+
+/// 请求中 API key 的默认参数名
+const DEFAULT_API_KEY_PARAM: &str = "apikey";
+
+
+/// 内置的链：名称、别名、chain id
+const BUILTIN_CHAINS: &[(&str, &[&str], u32)] = &[
+    ("eth", &["ethereum", "mainnet"], 1),
+    ("bsc", &["bnb", "binance"], 56),
+    ("ftm", &["fantom"], 250),
+    ("pg", &["polygon", "matic"], 137),
+    ("avax", &["avalanche", "snowtrace"], 43114),
+    ("arb", &["arbitrum"], 42161),
+    ("op", &["optimism"], 10),
+    ("sepolia", &[], 11155111),
+    ("base", &[], 8453),
+    ("moonbeam", &[], 1284),
+    ("moonriver", &[], 1285),
+    ("cro", &["cronos"], 25),
+    ("merlin", &[], 4200),
+    ("bitlayer", &[], 200901),
+    ("mode", &[], 34443),
+    ("scroll", &[], 534352),
+    ("core", &["coredao"], 1116),
+    ("linea", &[], 59144),
+    ("ailayer", &[], 2649),
+    ("gnosis", &["xdai"], 100),
+    ("zora", &[], 7777777),
+];
+
+
+/// 区块浏览器类型，可在配置文件中为某条链指定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExplorerKind {
+    Etherscan,
+    Blockscout,
+    Sourcify,
+    Routescan,
+}
+
+impl fmt::Display for ExplorerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExplorerKind::Etherscan => write!(f, "etherscan"),
+            ExplorerKind::Blockscout => write!(f, "blockscout"),
+            ExplorerKind::Sourcify => write!(f, "sourcify"),
+            ExplorerKind::Routescan => write!(f, "routescan"),
+        }
+    }
+}
+
+
+/// 某条链使用的区块浏览器
+#[derive(Debug, Clone)]
+pub struct Explorer {
+    pub name: String,
+    pub kind: ExplorerKind,
+    pub api_url: String,
+    /// 需要的 API key 名称
+    pub api_key: Option<String>,
+    /// 请求中 API key 的参数名，大多数为 `apikey`
+    pub api_key_param: String,
+    /// Etherscan V2 需要在请求中带上 `chainid`
+    pub chain_id: Option<u32>,
+    /// 每秒最多请求次数，使用同一个 `api_url` 的链共享限额
+    pub rate_limit: f64,
+}
+
+impl Explorer {
+    fn etherscan(name: &str, api_url: &str, api_key: Option<&str>) -> Self {
+        Explorer {
+            name: name.to_string(),
+            kind: ExplorerKind::Etherscan,
+            api_url: api_url.to_string(),
+            api_key: api_key.map(str::to_string),
+            api_key_param: DEFAULT_API_KEY_PARAM.to_string(),
+            chain_id: None,
+            rate_limit: 5.0,
+        }
+    }
+
+    fn etherscan_v2(chain_id: u32) -> Self {
+        Explorer {
+            chain_id: Some(chain_id),
+            ..Explorer::etherscan("etherscan", "https://api.etherscan.io/v2/api", Some("etherscan"))
+        }
+    }
+
+    fn blockscout(name: &str, api_url: &str) -> Self {
+        Explorer {
+            name: name.to_string(),
+            kind: ExplorerKind::Blockscout,
+            api_url: api_url.to_string(),
+            api_key: None,
+            api_key_param: DEFAULT_API_KEY_PARAM.to_string(),
+            chain_id: None,
+            rate_limit: 10.0,
+        }
+    }
+
+    fn sourcify(api_url: &str) -> Self {
+        Explorer {
+            name: "sourcify".to_string(),
+            kind: ExplorerKind::Sourcify,
+            api_url: api_url.to_string(),
+            api_key: None,
+            api_key_param: DEFAULT_API_KEY_PARAM.to_string(),
+            chain_id: None,
+            rate_limit: 10.0,
+        }
+    }
+
+    /// routescan 提供兼容 Etherscan 的接口，免费额度为每秒 2 次
+    fn routescan(chain_id: u32) -> Self {
+        Explorer {
+            name: "routescan".to_string(),
+            kind: ExplorerKind::Routescan,
+            api_url: format!("https://api.routescan.io/v2/network/mainnet/evm/{}/etherscan/api", chain_id),
+            api_key: None,
+            api_key_param: DEFAULT_API_KEY_PARAM.to_string(),
+            chain_id: None,
+            rate_limit: 2.0,
+        }
+    }
+
+    /// 指定类型的默认区块浏览器，Blockscout 没有默认地址
+    fn default_for(kind: ExplorerKind, chain_id: u32) -> Self {
+        match kind {
+            ExplorerKind::Etherscan => Explorer::etherscan_v2(chain_id),
+            ExplorerKind::Blockscout => Explorer::blockscout("blockscout", ""),
+            ExplorerKind::Sourcify => Explorer::sourcify(SOURCIFY_URL),
+            ExplorerKind::Routescan => Explorer::routescan(chain_id),
+        }
+    }
+
+    /// 内置的区块浏览器，按顺序尝试
+    ///
+    /// Etherscan V2 不支持的链使用各自的区块浏览器
+    fn builtin(chain_id: u32) -> Vec<Self> {
+        match chain_id {
+            1 => vec![
+                Explorer::etherscan_v2(chain_id),
+                Explorer::blockscout("eth blockscout", "https://eth.blockscout.com"),
+                Explorer::routescan(chain_id),
+            ],
+            10 => vec![Explorer::etherscan_v2(chain_id), Explorer::blockscout("optimism blockscout", "https://optimism.blockscout.com")],
+            137 => vec![Explorer::etherscan_v2(chain_id), Explorer::blockscout("polygon blockscout", "https://polygon.blockscout.com")],
+            8453 => vec![Explorer::etherscan_v2(chain_id), Explorer::blockscout("base blockscout", "https://base.blockscout.com")],
+            42161 => vec![Explorer::etherscan_v2(chain_id), Explorer::blockscout("arbitrum blockscout", "https://arbitrum.blockscout.com")],
+            4200 => vec![Explorer {
+                api_key_param: "api_key".to_string(),
+                ..Explorer::etherscan("merlin scan", "https://scan.merlinchain.io/api/", Some("merlinscan"))
+            }],
+            200901 => vec![Explorer::etherscan("bitlayer scan", "https://api.btrscan.com/scan/api", None)],
+            1116 => vec![Explorer::etherscan("core scan", "https://openapi.coredao.org/api", Some("corescan"))],
+            43114 => vec![Explorer { name: "snowtrace".to_string(), ..Explorer::routescan(chain_id) }],
+            2649 => vec![Explorer::blockscout("ailayer explorer", "https://mainnet-explorer.ailayer.xyz")],
+            34443 => vec![Explorer::blockscout("mode explorer", "https://explorer.mode.network")],
+            100 => vec![Explorer::blockscout("gnosis blockscout", "https://gnosis.blockscout.com")],
+            7777777 => vec![Explorer::blockscout("zora explorer", "https://explorer.zora.energy")],
+            _ => vec![Explorer::etherscan_v2(chain_id)],
+        }
+    }
+
+    /// 应用配置文件中的 `api_url`、`api_key`、`api_key_param`、`rate_limit`
+    fn apply(&mut self, chain: &str, overrides: &ExplorerConfig) -> Result<()> {
+        if let Some(api_url) = &overrides.api_url {
+            self.name = format!("{} {}", chain, self.kind);
+            self.api_url = api_url.clone();
+        }
+        if let Some(api_key) = &overrides.api_key {
+            self.api_key = Some(api_key.to_lowercase());
+        }
+        if let Some(param) = &overrides.api_key_param {
+            self.api_key_param = param.clone();
+        }
+        if let Some(rate_limit) = overrides.rate_limit {
+            if rate_limit.is_nan() || rate_limit <= 0.0 {
+                return Err(anyhow!("Chain '{}' has an invalid rate_limit {}", chain, rate_limit));
+            }
+            self.rate_limit = rate_limit;
+        }
+        if self.api_url.is_empty() {
+            return Err(anyhow!("Chain '{}' uses the {} explorer but has no api_url configured", chain, self.kind));
+        }
+        Ok(())
+    }
+}
+
+
+/// 一条链的信息，`name` 同时作为输出目录名
+#[derive(Debug, Clone)]
+pub struct Chain {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub chain_id: u32,
+    /// 获取源码时依次尝试的区块浏览器
+    pub explorers: Vec<Explorer>,
+    pub rpc_url: Option<String>,
+}
+
+impl Chain {
+    fn new(name: &str, aliases: &[&str], chain_id: u32) -> Self {
+        Chain {
+            name: name.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            chain_id,
+            explorers: Explorer::builtin(chain_id),
+            rpc_url: None,
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+    }
+
+    /// 应用配置文件中的覆盖项
+    ///
+    /// `explorers` 替换整个列表，`provider`、`api_url`、`api_key`、`api_key_param`、`rate_limit` 只修改第一个区块浏览器
+    fn apply(&mut self, overrides: &ChainConfig) -> Result<()> {
+        if let Some(chain_id) = overrides.chain_id {
+            if chain_id != self.chain_id {
+                self.chain_id = chain_id;
+                self.explorers = Explorer::builtin(chain_id);
+            }
+        }
+        if let Some(rpc_url) = &overrides.rpc_url {
+            self.rpc_url = Some(rpc_url.clone());
+        }
+        for alias in &overrides.aliases {
+            if !self.matches(alias) {
+                self.aliases.push(alias.to_lowercase());
+            }
+        }
+        if let Some(explorers) = &overrides.explorers {
+            if explorers.is_empty() {
+                return Err(anyhow!("Chain '{}' in the config file has an empty explorers list", self.name));
+            }
+            self.explorers = explorers.iter()
+                .map(|e| {
+                    let mut explorer = Explorer::default_for(e.provider, self.chain_id);
+                    explorer.apply(&self.name, e)?;
+                    Ok(explorer)
+                })
+                .collect::<Result<_>>()?;
+        }
+        let explorer = &mut self.explorers[0];
+        if let Some(kind) = overrides.provider.filter(|kind| *kind != explorer.kind) {
+            *explorer = Explorer::default_for(kind, self.chain_id);
+            explorer.name = format!("{} {}", self.name, kind);
+        }
+        explorer.apply(&self.name, &ExplorerConfig {
+            provider: explorer.kind,
+            api_url: overrides.api_url.clone(),
+            api_key: overrides.api_key.clone(),
+            api_key_param: overrides.api_key_param.clone(),
+            rate_limit: overrides.rate_limit,
+        })
+    }
+}
+
+
+/// 所有支持的链，内置的链加上配置文件中新增或覆盖的链
+#[derive(Debug, Clone)]
+pub struct ChainRegistry {
+    chains: Vec<Chain>,
+}
+
+impl ChainRegistry {
+    fn new() -> Self {
+        ChainRegistry {
+            chains: BUILTIN_CHAINS.iter()
+                .map(|(name, aliases, chain_id)| Chain::new(name, aliases, *chain_id))
+                .collect(),
+        }
+    }
+
+    /// 内置的链合并配置文件中的 `[chains]` 和 `[sourcify]`
+    pub fn load(config: &Config) -> Result<Self> {
+        let mut registry = ChainRegistry::new();
+        // 按名称排序，保证合并结果与配置文件中的顺序无关
+        let mut overrides: Vec<_> = config.chains.iter().collect();
+        overrides.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, overrides) in overrides {
+            match registry.chains.iter_mut().find(|c| c.matches(name)) {
+                Some(chain) => chain.apply(overrides)?,
+                None => {
+                    let chain_id = overrides.chain_id
+                        .ok_or_else(|| anyhow!("Chain '{}' in the config file must specify chain_id", name))?;
+                    let mut chain = Chain::new(&name.to_lowercase(), &[], chain_id);
+                    chain.apply(overrides)?;
+                    registry.chains.push(chain);
+                }
+            }
+        }
+        if config.sourcify.fallback {
+            registry.add_fallback(&Explorer::sourcify(config.sourcify.api_url.as_deref().unwrap_or(SOURCIFY_URL)));
+        }
+        Ok(registry)
+    }
+
+    /// 为还没有使用 `fallback` 类型数据源的链追加 `fallback`
+    fn add_fallback(&mut self, fallback: &Explorer) {
+        for chain in &mut self.chains {
+            if chain.explorers.iter().all(|e| e.kind != fallback.kind) {
+                chain.explorers.push(fallback.clone());
+            }
+        }
+    }
+
+    /// 通过名称、别名或者 chain id 查找链
+    pub fn resolve(&self, chain: &str) -> Result<&Chain, RetrieveError> {
+        let chain = chain.trim();
+        let found = match chain.parse::<u32>() {
+            Ok(chain_id) => self.chains.iter().find(|c| c.chain_id == chain_id),
+            Err(_) => self.chains.iter().find(|c| c.matches(chain)),
+        };
+        found.ok_or_else(|| RetrieveError::InvalidChain(format!("Invalid chain '{}'. Use --list to see supported chains", chain)))
+    }
+
+    /// 设置链的 JSON-RPC 地址，覆盖配置文件中的 `rpc_url`
+    pub fn set_rpc_url(&mut self, chain: &str, url: &str) -> Result<(), RetrieveError> {
+        let name = self.resolve(chain)?.name.clone();
+        let chain = self.chains.iter_mut().find(|c| c.name == name).unwrap();
+        chain.rpc_url = Some(url.to_string());
+        Ok(())
+    }
+
+    /// 按名称排序的所有链
+    pub fn sorted(&self) -> Vec<&Chain> {
+        let mut chains: Vec<_> = self.chains.iter().collect();
+        chains.sort_by(|a, b| a.name.cmp(&b.name));
+        chains
+    }
+}
+
+
+/// 测试用的链配置，`config` 中的 `{url}` 替换为 mockito 服务器地址，API key 都为 `test`
+#[cfg(test)]
+pub fn mock_registry(config: &str) -> (ChainRegistry, crate::config::ApiKeys) {
+    let config: Config = toml::from_str(&config.replace("{url}", &mockito::server_url())).unwrap();
+    let registry = ChainRegistry::load(&config).unwrap();
+    let keys = crate::config::ApiKeys::new(["etherscan=test", "merlinscan=test"], &config).unwrap();
+    (registry, keys)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_chain() {
+        let registry = ChainRegistry::load(&Config::default()).unwrap();
+        assert_eq!(registry.resolve("pg").unwrap().name, "pg");
+        assert_eq!(registry.resolve("Polygon").unwrap().name, "pg");
+        assert_eq!(registry.resolve("137").unwrap().name, "pg");
+        assert_eq!(registry.resolve("arbitrum").unwrap().chain_id, 42161);
+        assert!(registry.resolve("unknown").is_err());
+    }
+
+    #[test]
+    fn test_load_config_chains() {
+        let config: Config = toml::from_str(r#"
+            [chains.celo]
+            chain_id = 42220
+            aliases = ["celo-mainnet"]
+            provider = "blockscout"
+            api_url = "https://celo.blockscout.com"
+
+            [chains.polygon]
+            api_key = "polygonscan"
+            rate_limit = 10
+            rpc_url = "https://polygon-rpc.com"
+        "#).unwrap();
+        let registry = ChainRegistry::load(&config).unwrap();
+        let celo = registry.resolve("celo-mainnet").unwrap();
+        assert_eq!(celo.chain_id, 42220);
+        let kinds: Vec<_> = celo.explorers.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [ExplorerKind::Blockscout, ExplorerKind::Sourcify]);
+        let pg = registry.resolve("137").unwrap();
+        assert_eq!(pg.explorers[0].api_key.as_deref(), Some("polygonscan"));
+        assert_eq!(pg.explorers[0].rate_limit, 10.0);
+        assert_eq!(pg.rpc_url.as_deref(), Some("https://polygon-rpc.com"));
+        let mut registry = registry;
+        registry.set_rpc_url("137", "http://localhost:8545").unwrap();
+        assert_eq!(registry.resolve("polygon").unwrap().rpc_url.as_deref(), Some("http://localhost:8545"));
+        assert!(registry.set_rpc_url("unknown", "http://localhost:8545").is_err());
+
+        let custom: Config = toml::from_str(r#"
+            [sourcify]
+            fallback = false
+
+            [chains.eth]
+            explorers = [
+                { provider = "routescan" },
+                { provider = "blockscout", api_url = "https://eth.blockscout.com" },
+            ]
+        "#).unwrap();
+        let registry = ChainRegistry::load(&custom).unwrap();
+        let kinds: Vec<_> = registry.resolve("eth").unwrap().explorers.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [ExplorerKind::Routescan, ExplorerKind::Blockscout]);
+
+        let missing_id: Config = toml::from_str("[chains.unknown]\nprovider = \"etherscan\"").unwrap();
+        assert!(ChainRegistry::load(&missing_id).is_err());
+    }
+}
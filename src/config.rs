@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+use crate::address::AddressCase;
+use crate::chain::ExplorerKind;
+use crate::error::RetrieveError;
+
+/// 配置文件名
+pub const CONFIG_FILE: &str = "retriever.toml";
+
+
+/// 配置文件内容
+///
+/// ```toml
+/// [defaults]
+/// output = "./output"
+/// jobs = 4
+/// address_case = "checksum"
+/// skip_existing = false
+///
+/// [api_keys]
+/// etherscan = "..."
+///
+/// [sourcify]
+/// fallback = true
+///
+/// [http]
+/// timeout_secs = 30
+/// retries = 3
+///
+/// [cache]
+/// ttl_secs = 86400
+///
+/// [chains.celo]
+/// chain_id = 42220
+/// aliases = ["celo-mainnet"]
+/// provider = "blockscout"
+/// api_url = "https://celo.blockscout.com"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub defaults: Defaults,
+    #[serde(default)]
+    pub api_keys: HashMap<String, String>,
+    #[serde(default)]
+    pub chains: HashMap<String, ChainConfig>,
+    #[serde(default)]
+    pub sourcify: SourcifyConfig,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+}
+
+/// 命令行参数的默认值，命令行中指定的值优先
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    pub output: Option<String>,
+    /// 批量模式的并发数
+    pub jobs: Option<usize>,
+    /// 目录名称中地址的大小写
+    pub address_case: Option<AddressCase>,
+    /// 批量模式中跳过已经下载完整的地址
+    pub skip_existing: Option<bool>,
+}
+
+/// 请求超时和失败重试，未设置的项使用默认值
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    pub timeout_secs: Option<u64>,
+    pub retries: Option<u32>,
+    /// 第一次重试前等待的毫秒数，之后每次翻倍
+    pub backoff_ms: Option<u64>,
+}
+
+/// 区块浏览器响应的本地缓存
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    /// 默认启用
+    pub enabled: Option<bool>,
+    /// 默认为 `$XDG_CACHE_HOME/smart_contract_retriever/`
+    pub dir: Option<String>,
+    /// 缓存的有效秒数
+    pub ttl_secs: Option<u64>,
+}
+
+/// 区块浏览器没有返回源码时是否尝试 Sourcify
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourcifyConfig {
+    pub api_url: Option<String>,
+    #[serde(default = "default_true")]
+    pub fallback: bool,
+}
+
+impl Default for SourcifyConfig {
+    fn default() -> Self {
+        SourcifyConfig {
+            api_url: None,
+            fallback: true,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 新增一条链，或者覆盖内置链的区块浏览器设置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    /// 新增的链必须指定
+    pub chain_id: Option<u32>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub provider: Option<ExplorerKind>,
+    pub api_url: Option<String>,
+    /// 使用的 API key 名称，对应 `[api_keys]` 中的条目
+    pub api_key: Option<String>,
+    /// 请求中 API key 的参数名，默认为 `apikey`
+    pub api_key_param: Option<String>,
+    /// 每秒最多请求次数
+    pub rate_limit: Option<f64>,
+    /// JSON-RPC 节点，用于识别代理合约
+    pub rpc_url: Option<String>,
+    /// 依次尝试的区块浏览器，替换内置的列表
+    pub explorers: Option<Vec<ExplorerConfig>>,
+}
+
+/// `explorers` 列表中的一项
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExplorerConfig {
+    pub provider: ExplorerKind,
+    pub api_url: Option<String>,
+    pub api_key: Option<String>,
+    pub api_key_param: Option<String>,
+    pub rate_limit: Option<f64>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// 加载配置文件，未通过 `--config` 指定时依次查找当前目录和
+    /// `$XDG_CONFIG_HOME/smart_contract_retriever/`，都不存在则使用默认配置
+    pub fn discover(path: Option<&Path>) -> Result<Self> {
+        if let Some(path) = path {
+            return Config::load(path);
+        }
+        let mut candidates = vec![PathBuf::from(CONFIG_FILE)];
+        if let Some(dir) = config_home() {
+            candidates.push(dir.join("smart_contract_retriever").join(CONFIG_FILE));
+        }
+        match candidates.into_iter().find(|p| p.is_file()) {
+            Some(path) => Config::load(&path),
+            None => Ok(Config::default()),
+        }
+    }
+}
+
+
+fn config_home() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+
+/// 默认的缓存目录 `$XDG_CACHE_HOME/smart_contract_retriever/`，`XDG_CACHE_HOME` 未设置时使用 `~/.cache`
+pub fn cache_home() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache").map(|dir| dir.join("smart_contract_retriever"))
+}
+
+
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    match env::var_os(var) {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)),
+    }
+}
+
+
+/// 各个区块浏览器的 API key
+///
+/// 优先级：命令行参数 > 环境变量 `{NAME}_API_KEY` > 配置文件
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    cli: HashMap<String, String>,
+    file: HashMap<String, String>,
+}
+
+impl ApiKeys {
+    /// `cli` 为 `--api-key` 参数的值，格式为 `name=key`
+    pub fn new<'a>(cli: impl IntoIterator<Item = &'a str>, config: &Config) -> Result<Self> {
+        let mut keys = HashMap::new();
+        for arg in cli {
+            let (name, key) = arg.split_once('=')
+                .ok_or_else(|| anyhow!("Invalid --api-key value '{}', expected NAME=KEY", arg))?;
+            keys.insert(name.trim().to_lowercase(), key.trim().to_string());
+        }
+        Ok(ApiKeys {
+            cli: keys,
+            file: config.api_keys.iter()
+                .map(|(name, key)| (name.to_lowercase(), key.clone()))
+                .collect(),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        if let Some(key) = self.cli.get(name) {
+            return Some(key.clone());
+        }
+        if let Ok(key) = env::var(env_var(name)) {
+            if !key.is_empty() {
+                return Some(key);
+            }
+        }
+        self.file.get(name).cloned()
+    }
+
+    /// 获取 `name` 对应的 API key，不存在时返回包含配置方法的错误
+    pub fn require(&self, name: &str, chain: &str) -> Result<String, RetrieveError> {
+        self.get(name).ok_or_else(|| RetrieveError::InvalidApiKey(format!(
+            "Missing {} API key for chain '{}': set {}, pass --api-key {}=<KEY>, or add `{}` under [api_keys] in the config file",
+            name, chain, env_var(name), name, name
+        )))
+    }
+}
+
+
+fn env_var(name: &str) -> String {
+    format!("{}_API_KEY", name.to_uppercase())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_key_precedence() {
+        let config: Config = toml::from_str("[api_keys]\nlineascan = \"file\"\ncorescan = \"file\"").unwrap();
+        let keys = ApiKeys::new(["LineaScan=cli"], &config).unwrap();
+        assert_eq!(keys.get("lineascan").as_deref(), Some("cli"));
+        assert_eq!(keys.get("corescan").as_deref(), Some("file"));
+        assert!(keys.require("merlinscan", "merlin").unwrap_err().to_string().contains("MERLINSCAN_API_KEY"));
+        assert!(ApiKeys::new(["no-separator"], &config).is_err());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(r#"
+            [defaults]
+            output = "./contracts"
+            address_case = "lowercase"
+
+            [chains.gnosis]
+            chain_id = 100
+            provider = "blockscout"
+            api_url = "https://gnosis.blockscout.com"
+        "#).unwrap();
+        assert_eq!(config.defaults.output.as_deref(), Some("./contracts"));
+        assert_eq!(config.defaults.jobs, None);
+        assert_eq!(config.defaults.address_case, Some(AddressCase::Lowercase));
+        let gnosis = &config.chains["gnosis"];
+        assert_eq!(gnosis.chain_id, Some(100));
+        assert_eq!(gnosis.provider, Some(ExplorerKind::Blockscout));
+        assert!(toml::from_str::<Config>("[chains.eth]\nprovder = \"etherscan\"").is_err());
+    }
+}
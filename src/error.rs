@@ -0,0 +1,149 @@
+use std::fmt;
+use std::io;
+
+
+/// 获取合约源码时可能出现的错误，用于区分批量模式中的失败原因和进程退出码
+#[derive(Debug)]
+pub enum RetrieveError {
+    /// 所有数据源都没有已验证的源码
+    NotVerified(String),
+    /// 区块浏览器的请求频率限制
+    RateLimited(String),
+    /// API key 缺失或被区块浏览器拒绝
+    InvalidApiKey(String),
+    InvalidAddress(String),
+    InvalidChain(String),
+    /// 区块浏览器返回的数据无法解析
+    ParseError(String),
+    Network(String),
+    Io(String),
+    /// 区块浏览器返回的其他错误
+    Api(String),
+    /// 源码路径包含 NUL 字节或者清理后为空，无法安全保存
+    UnsafePath(String),
+    /// 只保存 ABI 时数据源没有返回 ABI，没有保存任何文件
+    NoAbi(String),
+    /// 批量模式中有处理失败的行，只用于进程退出码
+    PartialFailure(String),
+}
+
+impl RetrieveError {
+    /// 进程退出码，每种错误各不相同
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RetrieveError::NotVerified(_) => 2,
+            RetrieveError::RateLimited(_) => 3,
+            RetrieveError::InvalidApiKey(_) => 4,
+            RetrieveError::InvalidAddress(_) => 5,
+            RetrieveError::InvalidChain(_) => 6,
+            RetrieveError::ParseError(_) => 7,
+            RetrieveError::Network(_) => 8,
+            RetrieveError::Io(_) => 9,
+            RetrieveError::Api(_) => 10,
+            RetrieveError::UnsafePath(_) => 11,
+            RetrieveError::NoAbi(_) => 12,
+            RetrieveError::PartialFailure(_) => 13,
+        }
+    }
+
+    /// 错误类型名称，用于批量模式的汇总报告
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RetrieveError::NotVerified(_) => "not_verified",
+            RetrieveError::RateLimited(_) => "rate_limited",
+            RetrieveError::InvalidApiKey(_) => "invalid_api_key",
+            RetrieveError::InvalidAddress(_) => "invalid_address",
+            RetrieveError::InvalidChain(_) => "invalid_chain",
+            RetrieveError::ParseError(_) => "parse_error",
+            RetrieveError::Network(_) => "network",
+            RetrieveError::Io(_) => "io",
+            RetrieveError::Api(_) => "api",
+            RetrieveError::UnsafePath(_) => "unsafe_path",
+            RetrieveError::NoAbi(_) => "no_abi",
+            RetrieveError::PartialFailure(_) => "partial_failure",
+        }
+    }
+
+    /// 根据 Etherscan 风格接口 `status` 为 0 时的 `result`/`message` 判断错误类型
+    pub fn from_api_message(message: &str) -> Self {
+        let lower = message.to_lowercase();
+        if lower.contains("rate limit") {
+            RetrieveError::RateLimited(message.to_string())
+        } else if lower.contains("api key") || lower.contains("apikey") {
+            RetrieveError::InvalidApiKey(message.to_string())
+        } else if lower.contains("invalid address") {
+            RetrieveError::InvalidAddress(message.to_string())
+        } else if lower.contains("not verified") {
+            RetrieveError::NotVerified(message.to_string())
+        } else {
+            RetrieveError::Api(message.to_string())
+        }
+    }
+}
+
+impl fmt::Display for RetrieveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetrieveError::NotVerified(address) => write!(f, "Contract {} is not verified", address),
+            RetrieveError::RateLimited(msg) => write!(f, "Rate limited: {}", msg),
+            RetrieveError::InvalidApiKey(msg) => write!(f, "Invalid API key: {}", msg),
+            RetrieveError::InvalidAddress(msg) => write!(f, "Invalid address: {}", msg),
+            RetrieveError::InvalidChain(msg) => write!(f, "{}", msg),
+            RetrieveError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            RetrieveError::Network(msg) => write!(f, "Network error: {}", msg),
+            RetrieveError::Io(msg) => write!(f, "IO error: {}", msg),
+            RetrieveError::Api(msg) => write!(f, "Explorer error: {}", msg),
+            RetrieveError::UnsafePath(msg) => write!(f, "Unsafe source path: {}", msg),
+            RetrieveError::NoAbi(address) => write!(f, "No ABI for {}", address),
+            RetrieveError::PartialFailure(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RetrieveError {}
+
+/// 将 provider 中的 `anyhow::Error` 归类
+impl From<anyhow::Error> for RetrieveError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<RetrieveError>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        let message = format!("{:#}", e);
+        if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+            if e.is_decode() {
+                return RetrieveError::ParseError(message);
+            }
+            if e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) {
+                return RetrieveError::RateLimited(message);
+            }
+            return RetrieveError::Network(message);
+        }
+        if e.is::<serde_json::Error>() {
+            return RetrieveError::ParseError(message);
+        }
+        if e.is::<io::Error>() {
+            return RetrieveError::Io(message);
+        }
+        RetrieveError::Api(message)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_errors() {
+        assert!(matches!(RetrieveError::from_api_message("Max rate limit reached"), RetrieveError::RateLimited(_)));
+        assert!(matches!(RetrieveError::from_api_message("Invalid API Key"), RetrieveError::InvalidApiKey(_)));
+        assert!(matches!(RetrieveError::from_api_message("Invalid Address format"), RetrieveError::InvalidAddress(_)));
+        assert!(matches!(RetrieveError::from_api_message("NOTOK"), RetrieveError::Api(_)));
+
+        let parse = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        assert!(matches!(RetrieveError::from(anyhow::Error::new(parse)), RetrieveError::ParseError(_)));
+        let typed = anyhow::Error::new(RetrieveError::NotVerified("0x0".to_string()));
+        assert_eq!(RetrieveError::from(typed).exit_code(), 2);
+    }
+}
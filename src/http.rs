@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, StatusCode};
+use serde_json::Value;
+
+use crate::cache::{self, Cache};
+use crate::error::RetrieveError;
+
+
+/// 令牌桶限流，`per_second` 为每秒允许的请求数
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: f64) -> Self {
+        let burst = per_second.max(1.0);
+        RateLimiter {
+            per_second,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// 等待直到可以发出下一个请求
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+                bucket.updated = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_second)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+
+/// 获取 `key` 对应的限流器，同一个区块浏览器的所有请求共享一个限流器
+///
+/// 第一次获取时的 `per_second` 生效
+pub fn limiter(key: &str, per_second: f64) -> Arc<RateLimiter> {
+    static LIMITERS: OnceLock<Mutex<HashMap<String, Arc<RateLimiter>>>> = OnceLock::new();
+    let mut limiters = LIMITERS.get_or_init(Default::default).lock().unwrap();
+    limiters.entry(key.to_string())
+        .or_insert_with(|| Arc::new(RateLimiter::new(per_second)))
+        .clone()
+}
+
+
+/// 请求超时和重试设置
+#[derive(Debug, Clone)]
+pub struct HttpSettings {
+    pub timeout: Duration,
+    /// 第一次请求失败后最多重试的次数
+    pub retries: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// GET 响应的本地缓存，`None` 时不使用缓存
+    pub cache: Option<Arc<Cache>>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        HttpSettings {
+            timeout: Duration::from_secs(30),
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            cache: None,
+        }
+    }
+}
+
+impl HttpSettings {
+    /// 第 `attempt` 次重试前的等待时间，在 `[delay / 2, delay]` 之间随机，避免并发请求同时重试
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.backoff.saturating_mul(1 << attempt.min(16)).min(self.max_backoff);
+        let half = delay / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+}
+
+
+static SHARED: OnceLock<(reqwest::Client, HttpSettings)> = OnceLock::new();
+
+/// 设置所有数据源共用的超时和重试参数，需要在第一次请求之前调用
+pub fn init(settings: HttpSettings) -> Result<()> {
+    let client = reqwest::Client::builder().timeout(settings.timeout).build()?;
+    // 只有第一次设置生效
+    let _ = SHARED.set((client, settings));
+    Ok(())
+}
+
+fn shared() -> &'static (reqwest::Client, HttpSettings) {
+    SHARED.get_or_init(|| (reqwest::Client::new(), HttpSettings::default()))
+}
+
+
+/// 某个区块浏览器使用的 HTTP 客户端，所有请求都经过限流和重试
+#[derive(Debug, Clone)]
+pub struct Client {
+    inner: reqwest::Client,
+    settings: HttpSettings,
+    limiter: Arc<RateLimiter>,
+}
+
+impl Client {
+    /// 使用 `init` 设置的参数，`key` 相同的客户端共享限流器
+    pub fn new(key: &str, per_second: f64) -> Self {
+        let (inner, settings) = shared();
+        Client::with_settings(inner.clone(), settings.clone(), limiter(key, per_second))
+    }
+
+    pub fn with_settings(inner: reqwest::Client, settings: HttpSettings, limiter: Arc<RateLimiter>) -> Self {
+        Client { inner, settings, limiter }
+    }
+
+    /// 请求 `url` 并解析返回的 JSON，404 时返回 `None`
+    ///
+    /// 网络错误、429、5xx 以及 Etherscan 风格的频率限制响应会按指数退避重试，
+    /// 返回 `Retry-After` 时按其等待，但不超过 `max_backoff`。启用缓存时优先使用缓存，成功的响应写入缓存
+    pub async fn get_json(&self, url: &str) -> Result<Option<Value>> {
+        let Some(cache) = &self.settings.cache else {
+            return self.send_json(url, || self.inner.get(url)).await;
+        };
+        if let Some(body) = cache.get(url) {
+            return Ok(body);
+        }
+        if cache.offline {
+            return Err(RetrieveError::Network(format!("{} is not cached (offline mode)", cache::key(url))).into());
+        }
+        let result = self.send_json(url, || self.inner.get(url)).await;
+        if let Ok(body) = &result {
+            if body.as_ref().is_none_or(is_cacheable) {
+                if let Err(e) = cache.put(url, body.as_ref()) {
+                    println!("Failed to cache {}: {:#}", cache::key(url), e);
+                }
+            }
+        }
+        result
+    }
+
+    /// 以 JSON 格式 POST `body`，重试规则与 `get_json` 相同，不使用缓存
+    pub async fn post_json(&self, url: &str, body: &Value) -> Result<Option<Value>> {
+        if self.settings.cache.as_ref().is_some_and(|cache| cache.offline) {
+            return Err(RetrieveError::Network(format!("Cannot send requests to {} in offline mode", host(url))).into());
+        }
+        self.send_json(url, || self.inner.post(url).json(body)).await
+    }
+
+    async fn send_json(&self, url: &str, request: impl Fn() -> RequestBuilder) -> Result<Option<Value>> {
+        let mut attempt = 0;
+        loop {
+            let retry = attempt < self.settings.retries;
+            let wait = match self.try_send_json(url, request()).await {
+                Attempt::Done(result) => return result,
+                // 服务端要求的等待时间也不超过 `max_backoff`，避免一个响应让任务停住很久
+                Attempt::Retry(_, Some(wait)) if retry => wait.min(self.settings.max_backoff),
+                Attempt::Retry(_, None) if retry => self.settings.backoff(attempt),
+                Attempt::Retry(result, _) => return result,
+            };
+            attempt += 1;
+            println!("Retrying {} in {}ms ({}/{})", host(url), wait.as_millis(), attempt, self.settings.retries);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    async fn try_send_json(&self, url: &str, request: RequestBuilder) -> Attempt {
+        self.limiter.acquire().await;
+        let resp = match request.send().await {
+            Ok(resp) => resp,
+            Err(e) if e.is_timeout() || e.is_connect() => return Attempt::Retry(Err(redact(url, e)), None),
+            Err(e) => return Attempt::Done(Err(redact(url, e))),
+        };
+        let status = resp.status();
+        if status == StatusCode::NOT_FOUND {
+            return Attempt::Done(Ok(None));
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let error = RetrieveError::RateLimited(format!("HTTP 429 from {}", host(url)));
+            return Attempt::Retry(Err(error.into()), retry_after(resp.headers()));
+        }
+        if status.is_server_error() {
+            let wait = retry_after(resp.headers());
+            return Attempt::Retry(resp.error_for_status().map(|_| None).map_err(|e| redact(url, e)), wait);
+        }
+        let body = match resp.error_for_status() {
+            Ok(resp) => resp.text().await,
+            Err(e) => return Attempt::Done(Err(redact(url, e))),
+        };
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => return Attempt::Retry(Err(redact(url, e)), None),
+        };
+        match serde_json::from_str::<Value>(&body) {
+            Ok(value) if is_rate_limited(&value) => Attempt::Retry(Ok(Some(value)), None),
+            Ok(value) => Attempt::Done(Ok(Some(value))),
+            Err(e) => Attempt::Done(Err(RetrieveError::ParseError(format!("Invalid JSON response: {}", e)).into())),
+        }
+    }
+}
+
+
+/// 一次请求的结果，`Retry` 中为重试次数用完时返回的结果和服务端要求的等待时间
+enum Attempt {
+    Done(Result<Option<Value>>),
+    Retry(Result<Option<Value>>, Option<Duration>),
+}
+
+
+/// Etherscan 风格接口在超出频率限制时返回 200，`status` 为 `"0"`，`result` 为 `Max rate limit reached`
+fn is_rate_limited(value: &Value) -> bool {
+    value["status"] == "0"
+        && value["result"].as_str()
+            .is_some_and(|msg| matches!(RetrieveError::from_api_message(msg), RetrieveError::RateLimited(_)))
+}
+
+
+/// Etherscan 风格接口返回的错误（例如 API key 无效）不缓存，未验证的结果缓存
+fn is_cacheable(value: &Value) -> bool {
+    if value["status"] != "0" {
+        return true;
+    }
+    let message = value["result"].as_str().or(value["message"].as_str()).unwrap_or("");
+    matches!(RetrieveError::from_api_message(message), RetrieveError::NotVerified(_))
+}
+
+
+/// 只支持秒数格式的 `Retry-After`
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers.get(RETRY_AFTER)?
+        .to_str().ok()?
+        .trim().parse().ok()
+        .map(Duration::from_secs)
+}
+
+
+/// reqwest 的错误信息包含完整的 URL，其中可能有 API key，去掉 URL 只保留域名
+fn redact(url: &str, e: reqwest::Error) -> anyhow::Error {
+    anyhow::Error::new(e.without_url()).context(format!("Request to {} failed", host(url)))
+}
+
+
+/// URL 中的域名，用于日志和错误信息，避免输出其中的 key
+pub fn host(url: &str) -> String {
+    reqwest::Url::parse(url).ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::mock;
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        let limiter = RateLimiter::new(20.0);
+        let start = Instant::now();
+        // 前 20 个请求使用初始令牌，之后每 50ms 一个
+        for _ in 0..24 {
+            limiter.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(180));
+        assert!(Arc::ptr_eq(&limiter_for_test("a"), &limiter_for_test("a")));
+        assert!(!Arc::ptr_eq(&limiter_for_test("a"), &limiter_for_test("b")));
+    }
+
+    fn limiter_for_test(key: &str) -> Arc<RateLimiter> {
+        limiter(&format!("test:{}", key), 1.0)
+    }
+
+    fn test_client(retries: u32) -> Client {
+        let settings = HttpSettings {
+            timeout: Duration::from_secs(5),
+            retries,
+            backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(10),
+            cache: None,
+        };
+        Client::with_settings(reqwest::Client::new(), settings, Arc::new(RateLimiter::new(100.0)))
+    }
+
+    #[tokio::test]
+    async fn test_retry_server_errors_and_rate_limits() {
+        let url = format!("{}/retry", mockito::server_url());
+        let _unavailable = mock("GET", "/retry").with_status(503).expect(1).create();
+        let _limited = mock("GET", "/retry")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .expect(1)
+            .create();
+        let _payload = mock("GET", "/retry")
+            .with_body(r#"{"status": "0", "message": "NOTOK", "result": "Max rate limit reached"}"#)
+            .expect(1)
+            .create();
+        let _ok = mock("GET", "/retry").with_body(r#"{"status": "1"}"#).create();
+        let value = test_client(3).get_json(&url).await.unwrap().unwrap();
+        assert_eq!(value["status"], "1");
+
+        // 过长的 `Retry-After` 按 `max_backoff` 等待
+        let _slow = mock("GET", "/slow").with_status(503).with_header("retry-after", "86400").expect(1).create();
+        let _recovered = mock("GET", "/slow").with_body(r#"{"status": "1"}"#).create();
+        let (client, slow) = (test_client(1), format!("{}/slow", mockito::server_url()));
+        let value = tokio::time::timeout(Duration::from_secs(5), client.get_json(&slow)).await.unwrap().unwrap().unwrap();
+        assert_eq!(value["status"], "1");
+
+        let _always = mock("GET", "/limited").with_status(429).create();
+        let error = test_client(1).get_json(&format!("{}/limited", mockito::server_url())).await.unwrap_err();
+        assert!(matches!(RetrieveError::from(error), RetrieveError::RateLimited(_)));
+    }
+
+    #[tokio::test]
+    async fn test_errors_hide_api_key() {
+        let _unavailable = mock("GET", "/leak").match_query(mockito::Matcher::Any).with_status(503).create();
+        let url = format!("{}/leak?address=0x01&apikey=SUPERSECRET", mockito::server_url());
+        let error = RetrieveError::from(test_client(0).get_json(&url).await.unwrap_err());
+        assert!(matches!(error, RetrieveError::Network(_)));
+        assert!(error.to_string().contains("503"));
+        assert!(!error.to_string().contains("SUPERSECRET"));
+
+        // 连接失败
+        let error = RetrieveError::from(test_client(0).get_json("http://127.0.0.1:1/api?apikey=SUPERSECRET").await.unwrap_err());
+        assert!(!error.to_string().contains("SUPERSECRET"));
+    }
+
+    #[tokio::test]
+    async fn test_cached_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = |offline| {
+            let mut client = test_client(0);
+            client.settings.cache = Some(Arc::new(Cache::new(dir.path().to_path_buf(), cache::DEFAULT_TTL, offline)));
+            client
+        };
+        let url = format!("{}/cached?address=0x01&apikey=SECRET", mockito::server_url());
+        assert!(client(true).get_json(&url).await.is_err());
+
+        let cached = mock("GET", "/cached")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"status": "1", "result": []}"#)
+            .expect(1)
+            .create();
+        let invalid_key = mock("GET", "/invalid-key")
+            .with_body(r#"{"status": "0", "message": "NOTOK", "result": "Invalid API Key"}"#)
+            .expect(2)
+            .create();
+        client(false).get_json(&url).await.unwrap();
+        // 第二次以及离线模式都从缓存读取
+        assert_eq!(client(false).get_json(&url).await.unwrap().unwrap()["status"], "1");
+        assert_eq!(client(true).get_json(&url).await.unwrap().unwrap()["status"], "1");
+        cached.assert();
+        // 错误响应不缓存
+        let invalid = format!("{}/invalid-key", mockito::server_url());
+        client(false).get_json(&invalid).await.unwrap();
+        client(false).get_json(&invalid).await.unwrap();
+        invalid_key.assert();
+        assert!(client(true).post_json(&url, &Value::Null).await.is_err());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let settings = HttpSettings::default();
+        for attempt in 0..8 {
+            let delay = settings.backoff(attempt);
+            assert!(delay <= settings.max_backoff);
+            assert!(delay >= (settings.backoff * 2u32.pow(attempt)).min(settings.max_backoff) / 2);
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::provider::ContractSource;
+
+
+/// 源码在合约目录中的组织方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// 按数据源返回的路径保存
+    #[default]
+    Flat,
+    /// 可以直接 `forge build` 的 Foundry 项目
+    Foundry,
+    /// Hardhat 项目，npm 包的源码放在 `node_modules/` 下
+    Hardhat,
+}
+
+impl FromStr for Layout {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "flat" => Ok(Layout::Flat),
+            "foundry" => Ok(Layout::Foundry),
+            "hardhat" => Ok(Layout::Hardhat),
+            _ => Err(anyhow!("Invalid layout '{}', expected flat, foundry or hardhat", s)),
+        }
+    }
+}
+
+
+/// 按照 `layout` 生成合约目录中的文件，返回 (相对路径, 内容)
+pub fn project_files(layout: Layout, source: &ContractSource) -> Vec<(String, String)> {
+    match layout {
+        Layout::Flat => source.files.iter()
+            .map(|f| (f.path.clone(), f.content.clone()))
+            .collect(),
+        Layout::Foundry => foundry_files(source),
+        Layout::Hardhat => hardhat_files(source),
+    }
+}
+
+
+/// 路径中的 npm 包，例如 `@openzeppelin/contracts/token/ERC20/ERC20.sol`
+/// 或 `node_modules/solmate/src/tokens/ERC20.sol`，返回 (包名, 包内路径)
+fn split_package(path: &str) -> Option<(&str, &str)> {
+    let (path, in_node_modules) = match path.rfind("node_modules/") {
+        Some(i) => (&path[i + "node_modules/".len()..], true),
+        None => (path, false),
+    };
+    let end = if path.starts_with('@') {
+        let scope = path.find('/')?;
+        scope + 1 + path[scope + 1..].find('/')?
+    } else if in_node_modules {
+        path.find('/')?
+    } else {
+        return None;
+    };
+    Some((&path[..end], &path[end + 1..]))
+}
+
+
+/// 去掉绝对路径的前缀 `/`
+fn relative(path: &str) -> &str {
+    path.trim_start_matches('/')
+}
+
+
+/// Foundry 项目中的路径：npm 包放在 `lib/` 下，`lib/` 和 `src/` 下的文件保持不变，其他文件放在 `src/` 下
+fn foundry_path(path: &str) -> String {
+    if let Some((package, rest)) = split_package(path) {
+        return format!("lib/{}/{}", package.trim_start_matches('@').replace('/', "-"), rest);
+    }
+    let path = relative(path);
+    if path.starts_with("lib/") || path.starts_with("src/") {
+        path.to_string()
+    } else {
+        format!("src/{}", path)
+    }
+}
+
+
+fn foundry_files(source: &ContractSource) -> Vec<(String, String)> {
+    let mut files: Vec<_> = source.files.iter()
+        .map(|f| (foundry_path(&f.path), f.content.clone()))
+        .collect();
+    let settings = source.standard_json_input()
+        .map(|input| input["settings"].clone())
+        .unwrap_or(Value::Null);
+    files.push(("foundry.toml".to_string(), foundry_toml(source, &settings)));
+    let remappings = foundry_remappings(source, &settings);
+    if !remappings.is_empty() {
+        files.push(("remappings.txt".to_string(), remappings.join("\n") + "\n"));
+    }
+    files
+}
+
+
+/// 源码文件在编译时的路径需要通过 remapping 映射到新的位置
+///
+/// 原有的 remapping 按新的位置修改目标路径
+fn foundry_remappings(source: &ContractSource, settings: &Value) -> Vec<String> {
+    let mut remappings = BTreeSet::new();
+    if let Some(original) = settings["remappings"].as_array() {
+        for remapping in original.iter().filter_map(Value::as_str) {
+            if let Some((prefix, target)) = remapping.split_once('=') {
+                remappings.insert(format!("{}={}", prefix, foundry_prefix(target)));
+            }
+        }
+    }
+    for file in &source.files {
+        if let Some((_, rest)) = split_package(&file.path) {
+            let prefix = &file.path[..file.path.len() - rest.len()];
+            remappings.insert(format!("{}={}", prefix, foundry_prefix(prefix)));
+        } else if let Some((top, _)) = relative(&file.path).split_once('/') {
+            if top != "src" && top != "lib" {
+                remappings.insert(format!("{}/=src/{}/", top, top));
+            }
+        }
+    }
+    remappings.into_iter().collect()
+}
+
+
+/// 目录前缀对应的新位置，无法判断时保持不变
+fn foundry_prefix(prefix: &str) -> String {
+    let moved = foundry_path(&format!("{}x", prefix));
+    match moved.strip_suffix('x') {
+        Some(moved) if !prefix.is_empty() => moved.to_string(),
+        _ => prefix.to_string(),
+    }
+}
+
+
+/// `0.8.19+commit.7dd6d404` 中的版本号，Vyper 返回 `None`
+pub fn solc_version(source: &ContractSource) -> Option<String> {
+    let version = source.metadata.compiler_version.as_deref()?;
+    if version.starts_with("vyper") {
+        return None;
+    }
+    version.split('+').next().map(str::to_string)
+}
+
+
+fn foundry_toml(source: &ContractSource, settings: &Value) -> String {
+    let mut lines = vec![
+        "[profile.default]".to_string(),
+        "src = \"src\"".to_string(),
+        "out = \"out\"".to_string(),
+        "libs = [\"lib\"]".to_string(),
+    ];
+    if let Some(version) = solc_version(source) {
+        lines.push(format!("solc_version = \"{}\"", version));
+    }
+    if let Some(enabled) = settings["optimizer"]["enabled"].as_bool() {
+        lines.push(format!("optimizer = {}", enabled));
+    }
+    if let Some(runs) = settings["optimizer"]["runs"].as_u64() {
+        lines.push(format!("optimizer_runs = {}", runs));
+    }
+    if let Some(evm_version) = settings["evmVersion"].as_str() {
+        lines.push(format!("evm_version = \"{}\"", evm_version));
+    }
+    if settings["viaIR"].as_bool() == Some(true) {
+        lines.push("via_ir = true".to_string());
+    }
+    // 格式为 `文件:库名称:地址`
+    let mut libraries = Vec::new();
+    if let Some(files) = settings["libraries"].as_object() {
+        for (file, libs) in files {
+            for (name, address) in libs.as_object().into_iter().flatten() {
+                libraries.push(format!("\"{}:{}:{}\"", foundry_path(file), name, address.as_str().unwrap_or("")));
+            }
+        }
+    }
+    if !libraries.is_empty() {
+        lines.push(format!("libraries = [{}]", libraries.join(", ")));
+    }
+    lines.join("\n") + "\n"
+}
+
+
+/// Hardhat 项目中的路径：npm 包放在 `node_modules/` 下，其他文件放在 `contracts/` 下
+fn hardhat_path(path: &str) -> String {
+    if let Some((package, rest)) = split_package(path) {
+        return format!("node_modules/{}/{}", package, rest);
+    }
+    let path = relative(path);
+    if path.starts_with("contracts/") {
+        path.to_string()
+    } else {
+        format!("contracts/{}", path)
+    }
+}
+
+
+fn hardhat_files(source: &ContractSource) -> Vec<(String, String)> {
+    let mut files: Vec<_> = source.files.iter()
+        .map(|f| (hardhat_path(&f.path), f.content.clone()))
+        .collect();
+    let settings = source.standard_json_input()
+        .map(|input| input["settings"].clone())
+        .unwrap_or(Value::Null);
+    files.push(("hardhat.config.js".to_string(), hardhat_config(source, &settings)));
+    files.push(("package.json".to_string(), package_json(source)));
+    files
+}
+
+
+fn hardhat_config(source: &ContractSource, settings: &Value) -> String {
+    let Some(version) = solc_version(source) else {
+        return "module.exports = {};\n".to_string();
+    };
+    let mut solc_settings = Map::new();
+    for key in ["optimizer", "evmVersion", "viaIR", "metadata"] {
+        if let Some(value) = settings.get(key) {
+            solc_settings.insert(key.to_string(), value.clone());
+        }
+    }
+    if let Some(files) = settings["libraries"].as_object() {
+        let libraries: Map<String, Value> = files.iter()
+            .map(|(file, libs)| (hardhat_path(file), libs.clone()))
+            .collect();
+        solc_settings.insert("libraries".to_string(), Value::Object(libraries));
+    }
+    let solidity = json!({"version": version, "settings": solc_settings});
+    format!(
+        "/** @type import('hardhat/config').HardhatUserConfig */\nmodule.exports = {{\n  solidity: {},\n}};\n",
+        serde_json::to_string_pretty(&solidity).unwrap_or_default().replace('\n', "\n  ")
+    )
+}
+
+
+/// 根据 npm 包的源码生成 `package.json`，版本从 OpenZeppelin 风格的注释中识别并固定为该版本，
+/// 无法识别版本的包不写入，避免 `npm install` 用最新版本替换已验证的源码
+fn package_json(source: &ContractSource) -> String {
+    let mut dependencies: BTreeMap<String, Option<String>> = BTreeMap::new();
+    for file in &source.files {
+        if let Some((package, _)) = split_package(&file.path) {
+            let version = dependencies.entry(package.to_string()).or_default();
+            if version.is_none() {
+                *version = package_version(&file.content);
+            }
+        }
+    }
+    let dependencies: Map<String, Value> = dependencies.into_iter()
+        .filter_map(|(package, version)| Some((package, json!(version?))))
+        .collect();
+    let package = json!({
+        "name": format!("contract-{}", source.address.to_lowercase()),
+        "private": true,
+        "scripts": {"build": "hardhat compile"},
+        "dependencies": dependencies,
+        "devDependencies": {"hardhat": "^2.22.0"},
+    });
+    serde_json::to_string_pretty(&package).unwrap_or_default() + "\n"
+}
+
+
+/// 识别 `// OpenZeppelin Contracts (last updated v4.9.0)` 或 `// OpenZeppelin Contracts v4.4.1` 中的版本号
+fn package_version(content: &str) -> Option<String> {
+    for marker in ["(last updated v", "Contracts v"] {
+        if let Some(i) = content.find(marker) {
+            let version: String = content[i + marker.len()..].chars()
+                .take_while(|c| c.is_ascii_digit() || *c == '.')
+                .collect();
+            if !version.is_empty() {
+                return Some(version.trim_end_matches('.').to_string());
+            }
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(files: &[(String, String)]) -> Vec<&str> {
+        files.iter().map(|(p, _)| p.as_str()).collect()
+    }
+
+    #[test]
+    fn test_foundry_layout() {
+        let mut source = ContractSource::new("0x0");
+        source.add_file("contracts/Token.sol", "import \"@openzeppelin/contracts/token/ERC20/ERC20.sol\";");
+        source.add_file("@openzeppelin/contracts/token/ERC20/ERC20.sol", "contract ERC20 {}");
+        source.add_file("node_modules/solmate/src/auth/Owned.sol", "contract Owned {}");
+        source.add_file("lib/forge-std/src/Test.sol", "contract Test {}");
+        source.metadata.compiler_version = Some("0.8.19+commit.7dd6d404".to_string());
+        source.input = Some(json!({
+            "language": "Solidity",
+            "sources": {},
+            "settings": {
+                "optimizer": {"enabled": true, "runs": 1000},
+                "evmVersion": "paris",
+                "remappings": ["forge-std/=lib/forge-std/src/"],
+                "libraries": {"contracts/Token.sol": {"Math": "0x01"}}
+            }
+        }));
+
+        let files = project_files(Layout::Foundry, &source);
+        assert_eq!(paths(&files), [
+            "src/contracts/Token.sol",
+            "lib/openzeppelin-contracts/token/ERC20/ERC20.sol",
+            "lib/solmate/src/auth/Owned.sol",
+            "lib/forge-std/src/Test.sol",
+            "foundry.toml",
+            "remappings.txt",
+        ]);
+        let toml = &files[4].1;
+        assert!(toml.contains("solc_version = \"0.8.19\"\noptimizer = true\noptimizer_runs = 1000\nevm_version = \"paris\""));
+        assert!(toml.contains("libraries = [\"src/contracts/Token.sol:Math:0x01\"]"));
+        assert_eq!(files[5].1, "@openzeppelin/contracts/=lib/openzeppelin-contracts/\n\
+            contracts/=src/contracts/\n\
+            forge-std/=lib/forge-std/src/\n\
+            node_modules/solmate/=lib/solmate/\n");
+
+        let flat = project_files(Layout::Flat, &source);
+        assert_eq!(flat[1].0, "@openzeppelin/contracts/token/ERC20/ERC20.sol");
+        assert!("truffle".parse::<Layout>().is_err());
+    }
+
+    #[test]
+    fn test_hardhat_layout() {
+        let mut source = ContractSource::new("0xAB");
+        source.add_file("src/Token.sol", "import \"@openzeppelin/contracts/token/ERC20/ERC20.sol\";");
+        source.add_file("@openzeppelin/contracts/token/ERC20/ERC20.sol",
+                        "// SPDX-License-Identifier: MIT\n// OpenZeppelin Contracts (last updated v4.9.0) (token/ERC20/ERC20.sol)");
+        source.add_file("node_modules/solmate/src/auth/Owned.sol", "contract Owned {}");
+        source.metadata.compiler_version = Some("0.8.19+commit.7dd6d404".to_string());
+        source.metadata.optimization_enabled = Some(true);
+        source.metadata.optimization_runs = Some(200);
+
+        let files = project_files(Layout::Hardhat, &source);
+        assert_eq!(paths(&files), [
+            "contracts/src/Token.sol",
+            "node_modules/@openzeppelin/contracts/token/ERC20/ERC20.sol",
+            "node_modules/solmate/src/auth/Owned.sol",
+            "hardhat.config.js",
+            "package.json",
+        ]);
+        let config = &files[3].1;
+        assert!(config.contains("\"version\": \"0.8.19\""));
+        assert!(config.contains("\"runs\": 200"));
+        let package: Value = serde_json::from_str(&files[4].1).unwrap();
+        assert_eq!(package["name"], "contract-0xab");
+        assert_eq!(package["dependencies"], json!({"@openzeppelin/contracts": "4.9.0"}));
+    }
+}
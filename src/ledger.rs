@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::error::RetrieveError;
+
+/// 保存在输出目录中的任务记录文件名
+pub const LEDGER_FILE: &str = "ledger.jsonl";
+
+
+/// 一个地址的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Done,
+    NotVerified,
+    Failed,
+}
+
+/// 记录文件中的一行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub chain: String,
+    pub address: String,
+    pub status: JobStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+
+/// 批量任务的处理记录，每处理完一个地址追加一行 JSON
+///
+/// 同一个地址有多条记录时以最后一条为准，进程中途退出也不会丢失已经完成的记录
+#[derive(Debug)]
+pub struct Ledger {
+    path: PathBuf,
+    statuses: HashMap<(String, String), JobStatus>,
+    file: Mutex<File>,
+}
+
+impl Ledger {
+    /// 打开 `output` 目录中的记录文件，不存在时创建
+    pub fn open(output: &str) -> Result<Self> {
+        fs::create_dir_all(output)?;
+        let path = Path::new(output).join(LEDGER_FILE);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read ledger {}", path.display())),
+        };
+        let mut statuses = HashMap::new();
+        for line in content.lines() {
+            // 进程被中断时最后一行可能不完整
+            if let Ok(entry) = serde_json::from_str::<LedgerEntry>(line) {
+                statuses.insert(key(&entry.chain, &entry.address), entry.status);
+            }
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)
+            .with_context(|| format!("Failed to open ledger {}", path.display()))?;
+        if !content.is_empty() && !content.ends_with('\n') {
+            writeln!(file)?;
+        }
+        Ok(Ledger {
+            path,
+            statuses,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// 打开时记录文件中 `chain` 上 `address` 的状态
+    pub fn status(&self, chain: &str, address: &str) -> Option<JobStatus> {
+        self.statuses.get(&key(chain, address)).copied()
+    }
+
+    /// 追加一个地址的处理结果
+    pub fn record(&self, chain: &str, address: &str, result: &Result<(), RetrieveError>) -> Result<()> {
+        let (status, error) = match result {
+            Ok(()) => (JobStatus::Done, None),
+            Err(RetrieveError::NotVerified(_)) => (JobStatus::NotVerified, None),
+            Err(e) => (JobStatus::Failed, Some(e.to_string())),
+        };
+        let entry = LedgerEntry {
+            chain: chain.to_string(),
+            address: address.to_string(),
+            status,
+            error,
+        };
+        let line = serde_json::to_string(&entry)?;
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{}", line)
+            .with_context(|| format!("Failed to write ledger {}", self.path.display()))
+    }
+}
+
+
+fn key(chain: &str, address: &str) -> (String, String) {
+    (chain.to_lowercase(), address.to_lowercase())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ledger_last_entry_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().to_str().unwrap();
+        {
+            let ledger = Ledger::open(output).unwrap();
+            ledger.record("eth", "0xAA", &Err(RetrieveError::Network("timeout".to_string()))).unwrap();
+            ledger.record("eth", "0xbb", &Err(RetrieveError::NotVerified("0xbb".to_string()))).unwrap();
+            ledger.record("eth", "0xaa", &Ok(())).unwrap();
+        }
+        // 模拟写入一半时被中断
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(LEDGER_FILE)).unwrap();
+        write!(file, "{{\"chain\": \"eth\", \"addr").unwrap();
+
+        let ledger = Ledger::open(output).unwrap();
+        assert_eq!(ledger.status("ETH", "0xaa"), Some(JobStatus::Done));
+        assert_eq!(ledger.status("eth", "0xBB"), Some(JobStatus::NotVerified));
+        assert_eq!(ledger.status("eth", "0xcc"), None);
+        ledger.record("eth", "0xcc", &Ok(())).unwrap();
+        drop(ledger);
+        assert_eq!(Ledger::open(output).unwrap().status("eth", "0xcc"), Some(JobStatus::Done));
+    }
+}
@@ -1,365 +1,219 @@
-use std::collections::HashMap;
-use std::fmt::format;
+mod address;
+mod batch;
+mod cache;
+mod chain;
+mod config;
+mod error;
+mod http;
+mod layout;
+mod ledger;
+mod manifest;
+mod paths;
+mod provider;
+mod proxy;
+mod report;
+mod rpc;
+
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
-use reqwest;
-use serde_json::Value;
-use csv;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use clap::{App, Arg};
 use anyhow::{Result, anyhow};
-use lazy_static::lazy_static;
-use tokio;
-
-lazy_static! {
-    static ref CHAINS: HashMap<&'static str, u32> = {
-        let mut m = HashMap::new();
-        m.insert("eth", 1);
-        m.insert("bsc", 56);
-        m.insert("ftm", 250);
-        m.insert("pg", 137);
-        m.insert("avax", 43114);
-        m.insert("arb", 42161);
-        m.insert("op", 10);
-        m.insert("sepolia", 11155111);
-        m.insert("base", 8453);
-        m.insert("moonbeam", 1284);
-        m.insert("moonriver", 1285);
-        m.insert("cro", 25);
-        m.insert("merlin", 4200);
-        m.insert("bitlayer", 200901);
-        m.insert("mode", 34443);
-        m.insert("scroll", 534352);
-        m.insert("core", 1116);
-        m.insert("linea", 59144);
-        m.insert("ailayer", 2649);
-        m
-    };
-}
 
-
-/// 从 ailayer explorer 获取智能合约源代码
-async fn get_code_from_ailayer(basepath: &str, address: &str) -> Result<()> {
-    let api_url = format!("https://mainnet-explorer.ailayer.xyz/api/v2/smart-contracts/{}", address);
-    let resp: Value = reqwest::get(&api_url).await?.json().await?;
-
-    let main_sol = resp["source_code"].as_str()
-        .ok_or_else(|| anyhow!("Source code is missing or not a string"))?;
-    let main_path = format!("{}/{}", address,
-                            resp["file_path"].as_str().unwrap_or("Error: No main file path"));
-    save_code(basepath, "ailayer", &main_path, main_sol)?;
-    if let Some(additional_sources) = resp["additional_sources"].as_array() {
-        for source in additional_sources {
-            let code = source["source_code"].as_str()
-                .unwrap_or("Error: No additional_sources code");
-            let path = format!("{}/{}", address, source["file_path"].as_str()
-                .unwrap_or("Error: No additional_sources file path"));
-            save_code(basepath, "ailayer", &path, code)?;
-        }
-    }
-    Ok(())
+use address::AddressCase;
+use cache::Cache;
+use chain::{Chain, ChainRegistry};
+use config::{ApiKeys, Config};
+use error::RetrieveError;
+use http::HttpSettings;
+use layout::Layout;
+use manifest::Manifest;
+use provider::{ContractSource, Creation};
+use rpc::RpcClient;
+
+
+/// 保存源码的方式
+#[derive(Debug, Clone, Default)]
+struct OutputOptions {
+    basepath: String,
+    /// 只保存 `abi.json`
+    abi_only: bool,
+    layout: Layout,
+    /// 目录名称中地址的大小写
+    address_case: AddressCase,
 }
 
 
-/// 从 linea scan 获取智能合约源代码
-async fn get_code_from_linea(basepath: &str, address: &str) -> Result<()> {
-    let api_url =
-        format!("https://api.lineascan.build/api?module=contract&action=getsourcecode&address={}&apikey={yourApiKey}",
-                address);
-    let resp: Value = reqwest::get(&api_url).await?.json().await?;
-    if resp["status"] == "1" {
-        let result = resp["result"].as_array().ok_or(anyhow!("Result is not an array"))?;
-        for item in result {
-            let formatted_code = item["SourceCode"].as_str()
-                .unwrap_or("")
-                .strip_prefix("{")
-                .unwrap_or("")
-                .strip_suffix("}")
-                .unwrap_or("");
-            let outer_dict: Value = serde_json::from_str(&formatted_code)?;
-            if let Some(sources) = outer_dict["sources"].as_object() {
-                for (p, c) in sources {
-                    let path = format!("{}/{}", address, p);
-                    let code = c["content"].as_str().unwrap_or("Error: No content");
-                    save_code(basepath, "linea", &path, code)?;
-                }
-            }
-        }
-    } else {
-        println!("linea scan status error {}", address);
-    }
-    Ok(())
-}
+/// 代理合约最多向下获取几层实现合约
+const MAX_PROXY_DEPTH: usize = 4;
 
 
-/// 从 core scan 获取智能合约源代码
-async fn get_code_from_core(basepath: &str, address: &str) -> Result<()> {
-    let api_url =
-        format!("https://openapi.coredao.org/api?module=contract&action=getsourcecode&address={}&apikey={yourApiKey}",
-                address);
-    let resp: Value = reqwest::get(&api_url).await?.json().await?;
-    if resp["status"] == "1" {
-        let result = resp["result"].as_array().ok_or(anyhow!("Result is not an array"))?;
-        for item in result {
-            let formatted_code = item["SourceCode"].as_str()
-                .unwrap_or("")
-                .strip_prefix("{")
-                .unwrap_or("")
-                .strip_suffix("}")
-                .unwrap_or("");
-            let outer_dict: Value = serde_json::from_str(&formatted_code)?;
-            if let Some(sources) = outer_dict["sources"].as_object() {
-                for (p, c) in sources {
-                    let path = format!("{}/{}", address, p);
-                    let code = c["content"].as_str().unwrap_or("Error: No content");
-                    save_code(basepath, "core", &path, code)?;
-                }
+/// 从链对应的数据源获取智能合约源代码并保存
+///
+/// `chain` 可以是链名称、别名或者 chain id，源码统一保存在链名称对应的目录下。
+/// 地址必须是 20 字节的十六进制，请求中使用小写形式，目录名称按照 `output.address_case` 转换。
+/// 代理合约的实现合约保存在代理合约目录下的 `implementation/{address}/` 中
+async fn get_code(
+    output: &OutputOptions,
+    address: &str,
+    chain: &str,
+    registry: &ChainRegistry,
+    keys: &ApiKeys,
+) -> Result<(), RetrieveError> {
+    let address = address::parse(address)?;
+    let chain = registry.resolve(chain)?;
+    let rpc = chain.rpc_url.as_deref().map(RpcClient::new);
+    let mut dir = output.address_case.format(&address);
+    let mut source = fetch_source(output, chain, rpc.as_ref(), keys, &address, &dir).await?;
+    let mut visited = HashSet::from([address]);
+    loop {
+        let implementation = match proxy::detect(&source, rpc.as_ref()).await {
+            Ok(implementation) => implementation,
+            Err(e) => {
+                println!("Failed to detect proxy for {}: {:#}", source.address, e);
+                None
             }
-        }
-    } else {
-        println!("core scan status error {}", address);
-    }
-    Ok(())
-}
-
-
-/// 从 scrollscan 获取智能合约源代码
-async fn get_code_from_scroll(basepath: &str, address: &str) -> Result<()> {
-    let api_url =
-        format!("https://api.scrollscan.com/api?module=contract&action=getsourcecode&address={}&apikey={yourApiKey}",
-                address);
-    let resp: Value = reqwest::get(&api_url).await?.json().await?;
-    if resp["status"] == "1" {
-        let result = resp["result"].as_array().ok_or(anyhow!("Result is not an array"))?;
-        for item in result {
-            let formatted_code = item["SourceCode"].as_str()
-                .unwrap_or("")
-                .replace("{{", "{")
-                .replace("}}", "}");
-            let outer_dict: Value = serde_json::from_str(&formatted_code)?;
-            if let Some(sources) = outer_dict["sources"].as_object() {
-                for (p, c) in sources {
-                    let path = format!("{}/{}", address, p);
-                    let code = c["content"].as_str().unwrap_or("Error: No content");
-                    save_code(basepath, "scroll", &path, code)?;
-                }
+        };
+        let Some(mut implementation) = implementation else {
+            return persist(output, &chain.name, &dir, &source).map_err(RetrieveError::from);
+        };
+        implementation.address = match address::parse(&implementation.address) {
+            Ok(address) => address,
+            Err(e) => {
+                println!("Not following implementation of {}: {}", source.address, e);
+                return persist(output, &chain.name, &dir, &source).map_err(RetrieveError::from);
             }
+        };
+        let impl_name = output.address_case.format(&implementation.address);
+        let impl_dir = format!("{}/implementation/{}", dir, impl_name);
+        source.metadata.proxy = Some(true);
+        source.metadata.implementation = Some(implementation.address.clone());
+        source.metadata.proxy_type = Some(implementation.kind.to_string());
+        // 循环引用或者层数过多时只记录地址
+        if !visited.insert(implementation.address.clone()) || visited.len() > MAX_PROXY_DEPTH + 1 {
+            println!("Not following implementation {} of {}", implementation.address, source.address);
+            return persist(output, &chain.name, &dir, &source).map_err(RetrieveError::from);
         }
-    } else {
-        println!("scroll scan status error {}", address);
-    }
-    Ok(())
-}
-
-
-/// 从 merlinchain 获取智能合约源代码
-async fn get_code_from_merlin(basepath: &str, address: &str) -> Result<()> {
-    let api_url =
-        format!("https://scan.merlinchain.io/api/?module=contract&action=getsourcecode&address={}&api_key={yourApiKey}",
-                address);
-    let resp: Value = reqwest::get(&api_url).await?.json().await?;
-
-    if resp["status"] == "1" {
-        let result = resp["result"].as_array().ok_or(anyhow!("Result is not an array"))?;
-        for item in result {
-            let outer_dict: Value = serde_json::from_str(item["SourceCode"].as_str()
-                .unwrap_or("Error serde_json"))?;
-            if let Some(sources) = outer_dict["sources"].as_object() {
-                for (p, c) in sources {
-                    let path = format!("{}/{}", address, p);
-                    let code = c["content"].as_str().unwrap_or("Error: No content");
-                    save_code(basepath, "merlin", &path, code)?;
-                }
+        println!("{} is a proxy of {} ({})", source.address, implementation.address, implementation.kind);
+        let next = match fetch_source(output, chain, rpc.as_ref(), keys, &implementation.address, &impl_dir).await {
+            Ok(next) => next,
+            // 实现合约获取失败不影响代理合约本身
+            Err(e) => {
+                println!("Failed to retrieve implementation {}: {}", implementation.address, e);
+                return persist(output, &chain.name, &dir, &source).map_err(RetrieveError::from);
             }
-        }
-    } else {
-        println!("merlin scan status error {}", address);
+        };
+        source.metadata.implementation_path = Some(format!("implementation/{}", impl_name));
+        persist(output, &chain.name, &dir, &source)?;
+        source = next;
+        dir = impl_dir;
     }
-    Ok(())
 }
 
 
-/// 从 btrscan 获取智能合约源代码
-async fn get_code_from_bitlayer(basepath: &str, address: &str) -> Result<()> {
-    let api_url =
-        format!("https://api.btrscan.com/scan/api?module=contract&action=getsourcecode&address={}",
-                address);
-    let resp: Value = reqwest::get(&api_url).await?.json().await?;
-
-    if resp["status"] == 1 {
-        let result = resp["result"].as_array().ok_or(anyhow!("Result is not an array"))?;
-        for item in result {
-            let outer_dict: Value = serde_json::from_str(item["SourceCode"].as_str()
-                .unwrap_or("Error serde_json"))?;
-            if let Some(sources) = outer_dict["sources"].as_object() {
-                for (p, c) in sources {
-                    let path = format!("{}/{}", address, p);
-                    let code = c["content"].as_str().unwrap_or("Error: No content");
-                    save_code(basepath, "bitlayer", &path, code)?;
-                }
+/// 获取合约源码，合约未验证并且配置了 JSON-RPC 时将字节码保存到 `dir` 目录下
+async fn fetch_source(
+    output: &OutputOptions,
+    chain: &Chain,
+    rpc: Option<&RpcClient>,
+    keys: &ApiKeys,
+    address: &str,
+    dir: &str,
+) -> Result<ContractSource, RetrieveError> {
+    let result = provider::fetch_source(chain, keys, address).await;
+    if let (Err(RetrieveError::NotVerified(_)), Some(rpc)) = (&result, rpc) {
+        if !output.abi_only {
+            // 字节码只是补充输出，失败时仍然返回未验证
+            if let Err(e) = save_bytecode(output, chain, rpc, keys, address, dir).await {
+                println!("Failed to save bytecode of {}: {:#}", address, e);
             }
         }
-    } else {
-        println!("bitlayer scan status error {}", address);
     }
-    Ok(())
+    result
 }
 
 
-/// 从 Mode explorer 获取智能合约源代码
-async fn get_code_from_mode(basepath: &str, address: &str) -> Result<()> {
-    let api_url = format!("https://explorer.mode.network/api/v2/smart-contracts/{}", address);
-    let resp: Value = reqwest::get(&api_url).await?.json().await?;
-
-    let main_sol = resp["source_code"].as_str()
-        .ok_or_else(|| anyhow!("Source code is missing or not a string"))?;
-    let main_path = format!("{}/{}", address,
-                            resp["file_path"].as_str().unwrap_or("Error: No main file path"));
-    save_code(basepath, "mode", &main_path, main_sol)?;
-    if let Some(additional_sources) = resp["additional_sources"].as_array() {
-        for source in additional_sources {
-            let code = source["source_code"].as_str()
-                .unwrap_or("Error: No additional_sources code");
-            let path = format!("{}/{}", address, source["file_path"].as_str()
-                .unwrap_or("Error: No additional_sources file path"));
-            save_code(basepath, "mode", &path, code)?;
-        }
-    }
-    Ok(())
-}
-
-/// 从 Tenderly 获取智能合约源代码
+/// 运行时字节码保存为 `bytecode.hex`，能获取到创建字节码时保存为 `creation_bytecode.hex`，
+/// 最后写入 `manifest.json`
 ///
-/// NOTE: 已废弃，因为 Tenderly 不再提供免费的 API
-// async fn get_code_from_tenderly(basepath: &str, address: &str, chain: &str) -> Result<()> {
-//     let chain_id = CHAINS.get(chain).ok_or(anyhow!("Invalid chain"))?;
-//     let tdl_url = format!("https://api.tenderly.co/api/v1/public-contracts/{}/{}", chain_id, address);
-//     let resp: Value = reqwest::get(&tdl_url).await?.json().await?;
-//     let contract_info = resp["data"]["contract_info"]
-//         .as_array()
-//         .ok_or(anyhow!("Failed to extract contract_info array from API response"))?;
-//     for info in contract_info {
-//         let path = format!("{}/{}", address, info["path"].as_str().unwrap());
-//         let code_content = info["source"].as_str().unwrap();
-//         // println!("{}\n{}", path, code_content);
-//         save_code(basepath, chain, &path, code_content)?;
-//     }
-//     Ok(())
-// }
-
-
-/// 从 snowtrace 获取智能合约源代码
-async fn get_code_from_snowtrace(basepath: &str, address: &str) -> Result<()> {
-    let api_url = format!("https://api.routescan.io/v2/network/mainnet/evm/43114/etherscan/api?module=contract&action=getsourcecode&address={}", address);
-    let resp: Value = reqwest::get(&api_url).await?.json().await?;
-    if resp["status"] == "1" {
-        let result = resp["result"].as_array().ok_or(anyhow!("Result is not an array"))?;
-        for item in result {
-            let code = item["SourceCode"].as_str()
-                .unwrap_or("Error: No source code");
-            if code.is_empty() {
-                println!("Empty source code for {}", address);
-                continue;
-            }
-            let trimmed = code.trim();
-            if trimmed.starts_with("{{") && trimmed.ends_with("}}") {
-                let formatted_code = item["SourceCode"].as_str()
-                    .unwrap_or("")
-                    .strip_prefix("{")
-                    .unwrap_or("")
-                    .strip_suffix("}")
-                    .unwrap_or("");
-                let outer_dict: Value = serde_json::from_str(&formatted_code)?;
-                if let Some(sources) = outer_dict["sources"].as_object() {
-                    for (p, c) in sources {
-                        let path = format!("{}/{}", address, p);
-                        let code = c["content"].as_str().unwrap_or("Error: No content");
-                        save_code(basepath, "avax", &path, code)?;
-                    }
-                }
-            } else {
-                let contract_name = item["ContractName"].as_str().unwrap_or("Error: No contract name");
-                let path = format!("{}/{}.sol", address, contract_name);
-                save_code(basepath, "avax", &path, code)?;
-            }
-        }
-    } else {
-        println!("core scan status error {}", address);
+/// 创建字节码优先使用区块浏览器返回的，其次使用创建交易的 `input`
+async fn save_bytecode(
+    output: &OutputOptions,
+    chain: &Chain,
+    rpc: &RpcClient,
+    keys: &ApiKeys,
+    address: &str,
+    dir: &str,
+) -> Result<()> {
+    let code = rpc.get_code(address).await?;
+    if code == "0x" {
+        println!("No bytecode at {}", address);
+        return Ok(());
     }
-
-    Ok(())
+    save_code(&output.basepath, &chain.name, &format!("{}/bytecode.hex", dir), &code)?;
+    let mut files = vec!["bytecode.hex".to_string()];
+    let creation = match provider::fetch_creation(chain, keys, address).await {
+        Some(Creation { bytecode: Some(bytecode), .. }) => Some(bytecode),
+        Some(Creation { tx_hash: Some(tx_hash), .. }) => rpc.get_creation_input(&tx_hash).await?,
+        _ => None,
+    };
+    if let Some(creation) = creation {
+        save_code(&output.basepath, &chain.name, &format!("{}/creation_bytecode.hex", dir), &creation)?;
+        files.push("creation_bytecode.hex".to_string());
+    }
+    let manifest = Manifest { files, ..Manifest::default() };
+    save_manifest(&output.basepath, &chain.name, dir, &manifest)
 }
 
 
-/// 从 Etherscan 获取智能合约源代码
-async fn get_code_from_etherscan(basepath: &str, address: &str, chain: &str) -> Result<()> {
-    let chain_id = CHAINS.get(chain).ok_or(anyhow!("Invalid chain"))?;
-    let api_url = format!(
-        "https://api.etherscan.io/v2/api?chainid={}&module=contract&action=getsourcecode&address={}&apikey={yourApiKey}",
-        chain_id,
-        address
-    );
-
-    let resp: Value = reqwest::get(&api_url).await?.json().await?;
-    if resp["status"] == "1" {
-        let result = resp["result"].as_array().ok_or(anyhow!("Result is not an array"))?;
-        for item in result {
-            let code = item["SourceCode"].as_str()
-                .unwrap_or("Error: No source code");
-            if code.is_empty() {
-                println!("Empty source code for {}", address);
-                continue;
-            }
-            let trimmed = code.trim();
-            if trimmed.starts_with("{{") && trimmed.ends_with("}}") {
-                let formatted_code = item["SourceCode"].as_str()
-                    .unwrap_or("")
-                    .strip_prefix("{")
-                    .unwrap_or("")
-                    .strip_suffix("}")
-                    .unwrap_or("");
-                let outer_dict: Value = serde_json::from_str(&formatted_code)?;
-                if let Some(sources) = outer_dict["sources"].as_object() {
-                    for (p, c) in sources {
-                        let path = format!("{}/{}", address, p);
-                        let code = c["content"].as_str().unwrap_or("Error: No content");
-                        save_code(basepath, chain, &path, code)?;
-                    }
-                }
-            } else {
-                let contract_name = item["ContractName"].as_str().unwrap_or("Error: No contract name");
-                let path = format!("{}/{}.sol", address, contract_name);
-                save_code(basepath, chain, &path, code)?;
-            }
+/// 将合约源码保存到 `{basepath}/{chain}/{dir}/` 目录下，
+/// 编译信息保存为 `metadata.json`，ABI 保存为 `abi.json`，standard-json 输入保存为 `input.json`，
+/// 最后写入列出以上文件的 `manifest.json`。只保存 ABI 但是没有 ABI 时返回 `NoAbi`，不写入任何文件
+fn persist(output: &OutputOptions, chain: &str, dir: &str, source: &ContractSource) -> Result<()> {
+    let mut files = Vec::new();
+    match &source.metadata.abi {
+        Some(abi) => files.push(("abi.json".to_string(), serde_json::to_string_pretty(abi)?)),
+        None if output.abi_only => return Err(RetrieveError::NoAbi(source.address.clone()).into()),
+        None => {}
+    }
+    if !output.abi_only {
+        // 先清理数据源返回的路径，布局文件和 remapping 都基于清理后的路径生成
+        let safe = paths::sanitize_source(source)?;
+        files.extend(layout::project_files(output.layout, &safe));
+        if !safe.metadata.is_empty() {
+            files.push(("metadata.json".to_string(), serde_json::to_string_pretty(&safe.metadata)?));
+        }
+        // input.json 中保留原始路径，保证编译结果一致
+        if let Some(input) = source.standard_json_input() {
+            files.push(("input.json".to_string(), serde_json::to_string_pretty(&input)?));
         }
-    } else {
-        println!("core scan status error {}", address);
     }
-
-    Ok(())
+    for (path, content) in &files {
+        save_code(&output.basepath, chain, &format!("{}/{}", dir, path), content)?;
+    }
+    let manifest = Manifest {
+        files: files.into_iter().map(|(path, _)| path).collect(),
+        abi_only: output.abi_only,
+        layout: (!output.abi_only).then_some(output.layout),
+        implementation: source.metadata.implementation_path.clone(),
+    };
+    save_manifest(&output.basepath, chain, dir, &manifest)
 }
 
 
-/// 从 Tenderly 或者其他特定的链获取智能合约源代码
-async fn get_code(basepath: &str, address: &str, chain: &str) -> Result<()> {
-    let chain_id = CHAINS.get(chain).ok_or(anyhow!("Invalid chain"))?;
-    // tenderly 不支持的链，使用不同的获取方式
-    match chain_id {
-        4200 => get_code_from_merlin(basepath, address).await?,
-        200901 => get_code_from_bitlayer(basepath, address).await?,
-        1116 => get_code_from_core(basepath, address).await?,
-        43114 => get_code_from_snowtrace(basepath, address).await?,
-        2649 => get_code_from_ailayer(basepath, address).await?,
-        34443 => get_code_from_mode(basepath, address).await?,
-        _ => {
-            get_code_from_etherscan(basepath, address, chain).await?;
-        }
-    }
-    Ok(())
+/// 在合约目录的其他文件都保存之后调用
+fn save_manifest(basepath: &str, chain: &str, dir: &str, manifest: &Manifest) -> Result<()> {
+    let path = format!("{}/{}", dir, manifest::MANIFEST_FILE);
+    save_code(basepath, chain, &path, &serde_json::to_string_pretty(manifest)?)
 }
 
 
+/// 保存到 `{basepath}/{chain}/{path}`，`path` 会先经过 [`paths::sanitize`]，不会超出输出目录
 fn save_code(basepath: &str, chain: &str, path: &str, data: &str) -> Result<()> {
-    let output_dir = Path::new(basepath).join(chain).join(path);
+    let output_dir = Path::new(basepath).join(chain).join(paths::sanitize(path)?);
     fs::create_dir_all(output_dir.parent().unwrap())?;
     fs::write(&output_dir, data)?;
     println!("Saved: {}", output_dir.display());
@@ -368,7 +222,16 @@ fn save_code(basepath: &str, chain: &str, path: &str, data: &str) -> Result<()>
 
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {:#}", e);
+        let code = e.downcast_ref::<RetrieveError>().map_or(1, RetrieveError::exit_code);
+        std::process::exit(code);
+    }
+}
+
+
+async fn run() -> Result<()> {
     let matches = App::new("Smart Contract Source Code Retriever")
         .version("0.4.0")
         .author("Kong")
@@ -383,53 +246,210 @@ async fn main() -> Result<()> {
             .short('c')
             .long("chain")
             .value_name("CHAIN")
-            .help("Specify the chain (required in single mode)")
+            .help("Specify the chain name, alias or chain id (required in single mode, default chain in batch mode)")
             .takes_value(true))
         .arg(Arg::with_name("file")
             .short('f')
             .long("file")
             .value_name("FILE")
-            .help("Specify the file (required in batch mode). e.g. 0x0,eth")
+            .help("Specify the input file, or - for stdin (required in batch mode): CSV, TSV, JSONL or one address per line")
+            .takes_value(true))
+        .arg(Arg::with_name("address-col")
+            .long("address-col")
+            .value_name("COLUMN")
+            .help("Specify the address column of the input by header name or number [default: address or 1]")
+            .takes_value(true))
+        .arg(Arg::with_name("chain-col")
+            .long("chain-col")
+            .value_name("COLUMN")
+            .help("Specify the chain column of the input by header name or number [default: chain or 2]")
             .takes_value(true))
         .arg(Arg::with_name("output")
             .short('o')
             .long("output")
             .value_name("OUTPUT")
-            .help("Specify the output directory")
+            .help("Specify the output directory [default: ./output]")
+            .takes_value(true))
+        .arg(Arg::with_name("jobs")
+            .short('j')
+            .long("jobs")
+            .value_name("N")
+            .help("Specify the number of addresses processed concurrently in batch mode [default: 4]")
+            .takes_value(true))
+        .arg(Arg::with_name("report")
+            .long("report")
+            .value_name("REPORT")
+            .help("Write the batch summary to a .json or .csv file")
+            .takes_value(true))
+        .arg(Arg::with_name("layout")
+            .long("layout")
+            .value_name("LAYOUT")
+            .help("Specify how sources are laid out: flat, foundry or hardhat [default: flat]")
+            .takes_value(true))
+        .arg(Arg::with_name("address-case")
+            .long("address-case")
+            .value_name("CASE")
+            .help("Specify the case of addresses in directory names: checksum or lowercase [default: checksum]")
+            .takes_value(true))
+        .arg(Arg::with_name("abi-only")
+            .long("abi-only")
+            .help("Only save abi.json, without sources or metadata.json"))
+        .arg(Arg::with_name("resume")
+            .long("resume")
+            .help("Skip addresses already completed according to the ledger in the output directory"))
+        .arg(Arg::with_name("retry-failed")
+            .long("retry-failed")
+            .help("Only process addresses that failed according to the ledger in the output directory")
+            .conflicts_with("resume"))
+        .arg(Arg::with_name("skip-existing")
+            .long("skip-existing")
+            .help("Skip addresses whose output directory already has a complete manifest.json"))
+        .arg(Arg::with_name("force")
+            .long("force")
+            .help("Download every address again, even with --skip-existing or skip_existing in the config file")
+            .conflicts_with("skip-existing"))
+        .arg(Arg::with_name("timeout")
+            .long("timeout")
+            .value_name("SECS")
+            .help("Specify the timeout of each explorer request [default: 30]")
+            .takes_value(true))
+        .arg(Arg::with_name("retries")
+            .long("retries")
+            .value_name("N")
+            .help("Specify how many times a failed or rate limited request is retried [default: 3]")
+            .takes_value(true))
+        .arg(Arg::with_name("rpc-url")
+            .long("rpc-url")
+            .value_name("[CHAIN=]URL")
+            .help("Specify a JSON-RPC endpoint used for proxies and unverified bytecode, e.g. eth=http://localhost:8545 (can be repeated)")
             .takes_value(true)
-            .default_value("./output"))
+            .multiple_occurrences(true))
+        .arg(Arg::with_name("no-cache")
+            .long("no-cache")
+            .help("Do not read or write the local cache of explorer responses"))
+        .arg(Arg::with_name("offline")
+            .long("offline")
+            .help("Only use cached explorer responses, without sending any request")
+            .conflicts_with("no-cache"))
+        .arg(Arg::with_name("cache-ttl")
+            .long("cache-ttl")
+            .value_name("SECS")
+            .help("Specify how long cached explorer responses are used [default: 86400]")
+            .takes_value(true))
+        .arg(Arg::with_name("api-key")
+            .short('k')
+            .long("api-key")
+            .value_name("NAME=KEY")
+            .help("Specify an explorer API key, e.g. etherscan=XXXX (can be repeated)")
+            .takes_value(true)
+            .multiple_occurrences(true))
+        .arg(Arg::with_name("config")
+            .long("config")
+            .value_name("CONFIG")
+            .help("Specify the config file [default: ./retriever.toml or $XDG_CONFIG_HOME/smart_contract_retriever/retriever.toml]")
+            .takes_value(true))
         .arg(Arg::with_name("list")
             .short('l')
             .long("list")
             .help("List all supported chains"))
         .get_matches();
 
-    let output = matches.value_of("output").unwrap();
+    let config = Config::discover(matches.value_of("config").map(Path::new))?;
+    // 命令行参数优先于配置文件
+    let output = matches.value_of("output")
+        .or(config.defaults.output.as_deref())
+        .unwrap_or("./output");
+    let options = OutputOptions {
+        basepath: output.to_string(),
+        abi_only: matches.is_present("abi-only"),
+        layout: matches.value_of("layout").map(str::parse).transpose()?.unwrap_or_default(),
+        address_case: matches.value_of("address-case").map(str::parse).transpose()?
+            .or(config.defaults.address_case)
+            .unwrap_or_default(),
+    };
+    let jobs = parse_arg(matches.value_of("jobs"), "jobs")?
+        .or(config.defaults.jobs)
+        .unwrap_or(4);
+    if jobs == 0 {
+        return Err(anyhow!("The number of jobs must be at least 1"));
+    }
+    let mut settings = HttpSettings::default();
+    if let Some(timeout) = parse_arg(matches.value_of("timeout"), "timeout")?.or(config.http.timeout_secs) {
+        settings.timeout = Duration::from_secs(timeout);
+    }
+    if let Some(retries) = parse_arg(matches.value_of("retries"), "retries")?.or(config.http.retries) {
+        settings.retries = retries;
+    }
+    if let Some(backoff) = config.http.backoff_ms {
+        settings.backoff = Duration::from_millis(backoff);
+    }
+    let offline = matches.is_present("offline");
+    if !matches.is_present("no-cache") && config.cache.enabled.unwrap_or(true) {
+        let dir = config.cache.dir.as_ref().map(PathBuf::from)
+            .or_else(config::cache_home)
+            .ok_or_else(|| anyhow!("Cannot find a cache directory, set dir in the [cache] section of the config file"))?;
+        let ttl = parse_arg(matches.value_of("cache-ttl"), "cache-ttl")?
+            .or(config.cache.ttl_secs)
+            .map_or(cache::DEFAULT_TTL, Duration::from_secs);
+        settings.cache = Some(Arc::new(Cache::new(dir, ttl, offline)));
+    } else if offline {
+        return Err(anyhow!("--offline needs the cache, which is disabled in the config file"));
+    }
+    http::init(settings)?;
+    let mut registry = ChainRegistry::load(&config)?;
+    // 不带链名称时使用 --chain 指定的链
+    for value in matches.values_of("rpc-url").into_iter().flatten() {
+        let (chain, url) = match value.split_once('=') {
+            Some((chain, url)) if !chain.contains("://") => (chain, url),
+            _ => (matches.value_of("chain")
+                .ok_or_else(|| anyhow!("--rpc-url '{}' needs a chain, e.g. eth={}", value, value))?, value),
+        };
+        registry.set_rpc_url(chain, url)?;
+    }
+    let keys = ApiKeys::new(matches.values_of("api-key").into_iter().flatten(), &config)?;
 
     // 如果指定了 --list 参数，列出所有可用的链
     if matches.is_present("list") {
         println!("Available chains:");
-        for (chain, id) in CHAINS.iter() {
-            println!("{}: {}", chain, id);
+        println!("{:<12} {:>10}  {:<36}  ALIASES", "NAME", "CHAIN ID", "EXPLORERS");
+        for chain in registry.sorted() {
+            let explorers: Vec<_> = chain.explorers.iter().map(|e| e.kind.to_string()).collect();
+            println!("{:<12} {:>10}  {:<36}  {}",
+                     chain.name, chain.chain_id, explorers.join(" > "), chain.aliases.join(", "));
         }
         return Ok(());
     }
 
     // 处理输入：从文件读取或使用单个地址和链
     if let Some(file) = matches.value_of("file") {
-        let mut rdr = csv::Reader::from_path(file)?;
-        for result in rdr.records() {
-            let record = result?;
-            let address = record.get(0).ok_or(anyhow!("Invalid CSV file"))?;
-            let chain = record.get(1).ok_or(anyhow!("Invalid CSV file"))?;
-            // let chain_id = CHAINS.get(chain).ok_or(anyhow!("Invalid chain"))?;
-            get_code(output, address, chain).await?;
-            // 在每次请求之间等待200毫秒
-            tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        let resume = if matches.is_present("retry-failed") {
+            batch::Resume::FailedOnly
+        } else if matches.is_present("resume") {
+            batch::Resume::Unfinished
+        } else {
+            batch::Resume::Off
+        };
+        let ledger = ledger::Ledger::open(output)?;
+        let input = batch::InputOptions {
+            address_col: matches.value_of("address-col").map(str::to_string),
+            chain_col: matches.value_of("chain-col").map(str::to_string),
+            default_chain: matches.value_of("chain").map(str::to_string),
+        };
+        let (rows, invalid) = batch::read_jobs(file, &input)?;
+        let mut pending = batch::filter_jobs(batch::dedupe_jobs(rows, &registry), resume, &ledger, &registry);
+        let skip_existing = matches.is_present("skip-existing") || config.defaults.skip_existing.unwrap_or(false);
+        if skip_existing && !matches.is_present("force") {
+            pending = batch::skip_existing(pending, &options, &registry);
+        }
+        let outcomes = batch::run(&options, pending, jobs, &registry, &keys, &ledger).await?;
+        let report = report::Report::new(invalid.into_iter().chain(outcomes));
+        report.print();
+        if let Some(path) = matches.value_of("report") {
+            report.write(path)?;
         }
+        report.result()?;
     } else if let (Some(address), Some(chain)) = (matches.value_of("address"), matches.value_of("chain")) {
-        // let chain_id = CHAINS.get(chain).ok_or(anyhow!("Invalid chain"))?;
-        get_code(output, address, chain).await?;
+        get_code(&options, address, chain, &registry, &keys).await?;
     } else {
         println!("Invalid arguments. Use --help for usage instructions.");
     }
@@ -437,12 +457,16 @@ async fn main() -> Result<()> {
 }
 
 
+/// 解析数值类型的命令行参数
+fn parse_arg<T: FromStr>(value: Option<&str>, name: &str) -> Result<Option<T>> {
+    value.map(|v| v.parse().map_err(|_| anyhow!("Invalid {} '{}'", name, v)))
+        .transpose()
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs;
-    use std::path::Path;
-    use tokio;
 
     #[tokio::test]
     async fn test_get_code() {
@@ -473,7 +497,8 @@ mod tests {
         let chain = "avax";
 
         // 调用函数
-        let result = get_code(&basepath, address, chain).await;
+        let output = OutputOptions { basepath: basepath.to_string(), ..OutputOptions::default() };
+        let result = get_code(&output, address, chain, &ChainRegistry::load(&Config::default()).unwrap(), &ApiKeys::default()).await;
 
         // 检查函数是否成功执行
         assert!(result.is_ok(), "Function should return Ok");
@@ -492,4 +517,172 @@ mod tests {
         // 清理：删除临时目录
         // fs::remove_dir_all(basepath).unwrap();
     }
+
+    #[test]
+    fn test_persist_abi() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut source = ContractSource::new("0x01");
+        source.add_file("A.sol", "contract A {}");
+        source.metadata.abi = Some(serde_json::json!([{"type": "constructor", "inputs": []}]));
+
+        let mut output = OutputOptions {
+            basepath: dir.path().to_str().unwrap().to_string(),
+            abi_only: true,
+            ..OutputOptions::default()
+        };
+        persist(&output, "eth", "0x01", &source).unwrap();
+        let contract_dir = dir.path().join("eth").join("0x01");
+        let abi = fs::read_to_string(contract_dir.join("abi.json")).unwrap();
+        assert!(abi.contains("\n  {\n    \"inputs\""));
+        assert!(!contract_dir.join("A.sol").exists());
+
+        output.abi_only = false;
+        persist(&output, "eth", "0x01", &source).unwrap();
+        assert!(contract_dir.join("A.sol").exists());
+        assert!(contract_dir.join("metadata.json").exists());
+        assert!(contract_dir.join("input.json").exists());
+        assert!(manifest::is_complete(&contract_dir, false, Layout::Flat));
+
+        // 只保存 ABI 但是没有 ABI 时报错，不写入清单
+        source.metadata.abi = None;
+        output.abi_only = true;
+        let error = RetrieveError::from(persist(&output, "eth", "0x02", &source).unwrap_err());
+        assert_eq!(error.kind(), "no_abi");
+        assert!(!dir.path().join("eth").join("0x02").join(manifest::MANIFEST_FILE).exists());
+    }
+
+    #[test]
+    fn test_persist_sanitizes_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut source = ContractSource::new("0x01");
+        source.add_file("../../.bashrc", "echo pwned");
+        source.add_file("/etc/x", "");
+        source.add_file("contracts/A.sol", "contract A {}");
+
+        // 恶意的源码路径保存在合约目录内，原始路径记录在 metadata.json 中
+        for (layout, prefix) in [(Layout::Flat, ""), (Layout::Foundry, "src/")] {
+            let output = OutputOptions { basepath: dir.path().to_str().unwrap().to_string(), layout, ..OutputOptions::default() };
+            persist(&output, "eth", "0x01", &source).unwrap();
+            let contract_dir = dir.path().join("eth").join("0x01");
+            assert!(contract_dir.join(format!("{}__/__/.bashrc", prefix)).exists());
+            assert!(contract_dir.join(format!("{}etc/x", prefix)).exists());
+            let metadata: serde_json::Value = serde_json::from_str(&fs::read_to_string(contract_dir.join("metadata.json")).unwrap()).unwrap();
+            assert_eq!(metadata["original_paths"], serde_json::json!({"__/__/.bashrc": "../../.bashrc", "etc/x": "/etc/x"}));
+        }
+        assert!(!dir.path().join(".bashrc").exists());
+        let remappings = fs::read_to_string(dir.path().join("eth/0x01/remappings.txt")).unwrap();
+        assert!(!remappings.contains(".."), "{}", remappings);
+        assert!(remappings.contains("etc/=src/etc/"), "{}", remappings);
+
+        // 无法清理的路径单独归类，不算作区块浏览器错误
+        source.add_file("a\0b.sol", "");
+        let output = OutputOptions { basepath: dir.path().to_str().unwrap().to_string(), ..OutputOptions::default() };
+        let error = RetrieveError::from(persist(&output, "eth", "0x01", &source).unwrap_err());
+        assert_eq!(error.kind(), "unsafe_path");
+    }
+
+    #[test]
+    fn test_persist_keeps_generated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut source = ContractSource::new("0x01");
+        source.add_file("A.sol", "contract A {}");
+        source.add_file("abi.json", "[\"fake\"]");
+        source.add_file("manifest.json", "{}");
+        source.metadata.abi = Some(serde_json::json!([]));
+
+        // 与生成的文件同名的源码不会覆盖生成的文件
+        let output = OutputOptions { basepath: dir.path().to_str().unwrap().to_string(), ..OutputOptions::default() };
+        persist(&output, "eth", "0x01", &source).unwrap();
+        let contract_dir = dir.path().join("eth").join("0x01");
+        assert_eq!(fs::read_to_string(contract_dir.join("abi.json")).unwrap(), "[]");
+        assert_eq!(fs::read_to_string(contract_dir.join("_abi.json")).unwrap(), "[\"fake\"]");
+        let manifest: Manifest = serde_json::from_str(&fs::read_to_string(contract_dir.join(manifest::MANIFEST_FILE)).unwrap()).unwrap();
+        let unique: std::collections::HashSet<_> = manifest.files.iter().collect();
+        assert_eq!(unique.len(), manifest.files.len());
+        assert!(manifest.files.contains(&"_manifest.json".to_string()));
+        assert!(manifest::is_complete(&contract_dir, false, Layout::Flat));
+    }
+
+    #[tokio::test]
+    async fn test_get_code_follows_proxy() {
+        use mockito::{mock, Matcher};
+
+        let (registry, keys) = chain::mock_registry(r#"
+            [sourcify]
+            fallback = false
+
+            [chains.eth]
+            explorers = [{ provider = "etherscan", api_url = "{url}/proxy-api" }]
+        "#);
+        // 0x...0a 是 0x...0b 的代理，0x...0b 又指回 0x...0a
+        let contract = |address: &str, name: &str, implementation: &str| mock("GET", "/proxy-api")
+            .match_query(Matcher::UrlEncoded("address".to_string(), address.to_string()))
+            .with_body(serde_json::json!({"status": "1", "message": "OK", "result": [{
+                "SourceCode": format!("contract {} {{}}", name),
+                "ContractName": name,
+                "Proxy": if implementation.is_empty() { "0" } else { "1" },
+                "Implementation": implementation
+            }]}).to_string())
+            .create();
+        let _proxy = contract("0x000000000000000000000000000000000000000a", "Proxy", "0x000000000000000000000000000000000000000b");
+        let _implementation = contract("0x000000000000000000000000000000000000000b", "Token", "0x000000000000000000000000000000000000000a");
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = OutputOptions {
+            basepath: dir.path().to_str().unwrap().to_string(),
+            address_case: AddressCase::Lowercase,
+            ..OutputOptions::default()
+        };
+        get_code(&output, "0x000000000000000000000000000000000000000a", "eth", &registry, &keys).await.unwrap();
+
+        let proxy_dir = dir.path().join("eth").join("0x000000000000000000000000000000000000000a");
+        let impl_dir = proxy_dir.join("implementation").join("0x000000000000000000000000000000000000000b");
+        assert!(proxy_dir.join("Proxy.sol").exists());
+        assert!(impl_dir.join("Token.sol").exists());
+        assert!(!impl_dir.join("implementation").exists());
+        let metadata: serde_json::Value = serde_json::from_str(&fs::read_to_string(proxy_dir.join("metadata.json")).unwrap()).unwrap();
+        assert_eq!(metadata["implementation_path"], "implementation/0x000000000000000000000000000000000000000b");
+        assert_eq!(metadata["proxy_type"], "explorer");
+    }
+
+    #[tokio::test]
+    async fn test_get_code_saves_unverified_bytecode() {
+        use mockito::{mock, Matcher};
+        use serde_json::json;
+
+        let (registry, keys) = chain::mock_registry(r#"
+            [sourcify]
+            fallback = false
+
+            [chains.eth]
+            explorers = [{ provider = "etherscan", api_url = "{url}/unverified-api" }]
+            rpc_url = "{url}/unverified-rpc"
+        "#);
+        let _source = mock("GET", "/unverified-api")
+            .match_query(Matcher::UrlEncoded("action".to_string(), "getsourcecode".to_string()))
+            .with_body(json!({"status": "1", "message": "OK", "result": [{"SourceCode": "", "ABI": "Contract source code not verified"}]}).to_string())
+            .create();
+        let _creation = mock("GET", "/unverified-api")
+            .match_query(Matcher::UrlEncoded("action".to_string(), "getcontractcreation".to_string()))
+            .with_body(json!({"status": "1", "message": "OK", "result": [{"contractAddress": "0xdac17f958d2ee523a2206206994597c13d831ec7", "txHash": "0xabc"}]}).to_string())
+            .create();
+        let _code = mock("POST", "/unverified-rpc")
+            .match_body(Matcher::PartialJson(json!({"method": "eth_getCode", "params": ["0xdac17f958d2ee523a2206206994597c13d831ec7", "latest"]})))
+            .with_body(json!({"jsonrpc": "2.0", "id": 1, "result": "0x6001"}).to_string())
+            .create();
+        let _tx = mock("POST", "/unverified-rpc")
+            .match_body(Matcher::PartialJson(json!({"method": "eth_getTransactionByHash", "params": ["0xabc"]})))
+            .with_body(json!({"jsonrpc": "2.0", "id": 1, "result": {"to": null, "input": "0x60806001"}}).to_string())
+            .create();
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = OutputOptions { basepath: dir.path().to_str().unwrap().to_string(), ..OutputOptions::default() };
+        let result = get_code(&output, "0xDAC17F958D2EE523A2206206994597C13D831EC7", "eth", &registry, &keys).await;
+        assert!(matches!(result, Err(RetrieveError::NotVerified(_))));
+        // 目录名称使用 EIP-55 校验和形式
+        let contract_dir = dir.path().join("eth").join("0xdAC17F958D2ee523a2206206994597C13D831ec7");
+        assert_eq!(fs::read_to_string(contract_dir.join("bytecode.hex")).unwrap(), "0x6001");
+        assert_eq!(fs::read_to_string(contract_dir.join("creation_bytecode.hex")).unwrap(), "0x60806001");
+        assert!(manifest::is_complete(&contract_dir, false, Layout::Flat));
+    }
 }
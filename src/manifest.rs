@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::Layout;
+
+/// 清单文件名，在合约目录的其他文件都保存完成后最后写入
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+
+/// 合约目录中保存的文件，用于判断之前的下载是否完整
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// 相对于合约目录的路径
+    pub files: Vec<String>,
+    /// 是否只保存了 `abi.json`
+    #[serde(default)]
+    pub abi_only: bool,
+    /// 源码的组织方式，只保存了 ABI 或字节码时为 `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    /// 实现合约的目录，相对于合约目录，实现合约也需要完整
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub implementation: Option<String>,
+}
+
+
+/// `dir` 中有清单、清单至少列出一个文件并且这些文件都存在时为完整。
+/// 之前只保存了 ABI 的目录对于需要源码的下载不算完整，源码按其他布局保存的目录也不算完整
+pub fn is_complete(dir: &Path, abi_only: bool, layout: Layout) -> bool {
+    let Ok(content) = fs::read_to_string(dir.join(MANIFEST_FILE)) else {
+        return false;
+    };
+    let Ok(manifest) = serde_json::from_str::<Manifest>(&content) else {
+        return false;
+    };
+    if manifest.abi_only && !abi_only {
+        return false;
+    }
+    if !abi_only && manifest.layout.is_some_and(|saved| saved != layout) {
+        return false;
+    }
+    !manifest.files.is_empty()
+        && manifest.files.iter().all(|file| dir.join(file).is_file())
+        && manifest.implementation.is_none_or(|path| is_complete(&dir.join(path), abi_only, layout))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_complete() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_manifest = |manifest: &Manifest| {
+            fs::write(dir.path().join(MANIFEST_FILE), serde_json::to_string(manifest).unwrap()).unwrap();
+        };
+        assert!(!is_complete(dir.path(), false, Layout::Flat));
+
+        // 空清单
+        write_manifest(&Manifest::default());
+        assert!(!is_complete(dir.path(), true, Layout::Flat));
+
+        fs::write(dir.path().join("abi.json"), "[]").unwrap();
+        let mut manifest = Manifest { files: vec!["abi.json".to_string(), "A.sol".to_string()], ..Manifest::default() };
+        write_manifest(&manifest);
+        assert!(!is_complete(dir.path(), false, Layout::Flat));
+
+        fs::write(dir.path().join("A.sol"), "contract A {}").unwrap();
+        assert!(is_complete(dir.path(), false, Layout::Flat));
+
+        manifest.abi_only = true;
+        write_manifest(&manifest);
+        assert!(is_complete(dir.path(), true, Layout::Flat));
+        assert!(!is_complete(dir.path(), false, Layout::Flat));
+
+        // 按其他布局保存的源码需要重新下载，只需要 ABI 时不受影响
+        manifest.abi_only = false;
+        manifest.layout = Some(Layout::Flat);
+        write_manifest(&manifest);
+        assert!(is_complete(dir.path(), false, Layout::Flat));
+        assert!(!is_complete(dir.path(), false, Layout::Foundry));
+        assert!(is_complete(dir.path(), true, Layout::Foundry));
+
+        // 实现合约没有清单
+        manifest.abi_only = false;
+        manifest.implementation = Some("implementation/0x01".to_string());
+        write_manifest(&manifest);
+        assert!(!is_complete(dir.path(), false, Layout::Flat));
+    }
+}
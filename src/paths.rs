@@ -0,0 +1,115 @@
+use crate::error::RetrieveError;
+use crate::manifest::MANIFEST_FILE;
+use crate::provider::ContractSource;
+
+/// 合约目录中由程序生成的文件，源码不能使用这些路径，否则会覆盖生成的文件
+const RESERVED_FILES: [&str; 6] = ["abi.json", "metadata.json", "input.json", MANIFEST_FILE, "bytecode.hex", "creation_bytecode.hex"];
+
+/// 代理合约的实现合约保存在这个目录下
+const RESERVED_DIR: &str = "implementation";
+
+
+/// 将数据源返回的源码路径转换为安全的相对路径，保证保存时不会超出输出目录
+///
+/// - `\` 统一为 `/`
+/// - 去掉开头的 `/` 和 Windows 盘符（`C:`），绝对路径变为相对路径
+/// - 去掉空的和 `.` 组成部分，`..` 改写为 `__`
+/// - 包含 NUL 字节或者去掉以上部分后为空的路径直接拒绝
+pub fn sanitize(path: &str) -> Result<String, RetrieveError> {
+    if path.contains('\0') {
+        return Err(RetrieveError::UnsafePath(format!("{:?} contains a NUL byte", path)));
+    }
+    let normalized = path.replace('\\', "/");
+    let mut parts = Vec::new();
+    for (i, part) in normalized.split('/').enumerate() {
+        let part = match part {
+            "" | "." => continue,
+            ".." => "__",
+            _ if i == 0 && is_drive(part) => continue,
+            _ => part,
+        };
+        parts.push(part);
+    }
+    if parts.is_empty() {
+        return Err(RetrieveError::UnsafePath(format!("{:?} has no file name left", path)));
+    }
+    Ok(parts.join("/"))
+}
+
+
+/// 清理所有源码文件的路径，返回清理后的副本，需要在按布局转换路径之前调用
+///
+/// 与生成的文件或 `implementation/` 目录冲突的路径前面加上 `_`。被改写的路径记录在 `metadata.original_paths` 中，键为清理后的路径，值为数据源返回的原始路径
+pub fn sanitize_source(source: &ContractSource) -> Result<ContractSource, RetrieveError> {
+    let mut safe = source.clone();
+    for file in &mut safe.files {
+        let path = unreserve(sanitize(&file.path)?);
+        if path != file.path {
+            println!("Saving {} of {} as {}", file.path, source.address, path);
+            let original = std::mem::replace(&mut file.path, path.clone());
+            safe.metadata.original_paths.insert(path, original);
+        }
+    }
+    Ok(safe)
+}
+
+
+/// 第一级路径与保留的文件名或目录名相同时（不区分大小写）加上 `_` 前缀
+fn unreserve(path: String) -> String {
+    let first = path.split('/').next().unwrap_or_default();
+    let reserved = if first.len() == path.len() {
+        RESERVED_FILES.iter().any(|name| name.eq_ignore_ascii_case(&path))
+    } else {
+        first.eq_ignore_ascii_case(RESERVED_DIR)
+    };
+    if reserved { format!("_{}", path) } else { path }
+}
+
+
+/// `C:` 这样的 Windows 盘符
+fn is_drive(part: &str) -> bool {
+    let bytes = part.as_bytes();
+    bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_hostile_paths() {
+        assert_eq!(sanitize("contracts/Token.sol").unwrap(), "contracts/Token.sol");
+        assert_eq!(sanitize("@openzeppelin/contracts/token/ERC20.sol").unwrap(), "@openzeppelin/contracts/token/ERC20.sol");
+        // 相对路径逃逸
+        assert_eq!(sanitize("../../.bashrc").unwrap(), "__/__/.bashrc");
+        assert_eq!(sanitize("contracts/../../x.sol").unwrap(), "contracts/__/__/x.sol");
+        // 绝对路径
+        assert_eq!(sanitize("/etc/x").unwrap(), "etc/x");
+        assert_eq!(sanitize("//server/share/x.sol").unwrap(), "server/share/x.sol");
+        // Windows 路径
+        assert_eq!(sanitize("C:\\Windows\\x.sol").unwrap(), "Windows/x.sol");
+        assert_eq!(sanitize("c:/x.sol").unwrap(), "x.sol");
+        assert_eq!(sanitize("..\\..\\x.sol").unwrap(), "__/__/x.sol");
+        // 多余的分隔符和 `.`
+        assert_eq!(sanitize("./a//./b.sol").unwrap(), "a/b.sol");
+        // 拒绝
+        assert!(matches!(sanitize("a\0b.sol"), Err(RetrieveError::UnsafePath(_))));
+        assert!(sanitize("/").is_err());
+        assert!(sanitize("C:").is_err());
+        assert!(sanitize("").is_err());
+    }
+
+    #[test]
+    fn test_sanitize_reserved_paths() {
+        let mut source = ContractSource::new("0x01");
+        for path in ["abi.json", "./Metadata.json", "manifest.json", "implementation/0x02/A.sol", "contracts/abi.json", "input.json.sol"] {
+            source.add_file(path, "");
+        }
+        let safe = sanitize_source(&source).unwrap();
+        let paths: Vec<_> = safe.files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(paths, ["_abi.json", "_Metadata.json", "_manifest.json", "_implementation/0x02/A.sol", "contracts/abi.json", "input.json.sol"]);
+        assert_eq!(safe.metadata.original_paths["_abi.json"], "abi.json");
+        assert_eq!(safe.metadata.original_paths["_Metadata.json"], "./Metadata.json");
+    }
+}
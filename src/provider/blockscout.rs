@@ -0,0 +1,157 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+use super::{non_empty, normalize_compiler_version, normalize_hex, normalize_license, ContractMetadata, ContractSource, Creation, SourceProvider};
+use crate::http::Client;
+
+
+/// Blockscout 的 `/api/v2/smart-contracts/{address}` 接口
+///
+/// 适用于任意 Blockscout 实例，只需要配置对应的 `api_url`
+pub struct BlockscoutProvider {
+    name: String,
+    base_url: String,
+    client: Client,
+}
+
+impl BlockscoutProvider {
+    pub fn new(name: &str, base_url: &str, client: Client) -> Self {
+        BlockscoutProvider {
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl SourceProvider for BlockscoutProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self, address: &str) -> Result<ContractSource> {
+        let api_url = format!("{}/api/v2/smart-contracts/{}", self.base_url, address);
+        // 不是合约或者没有验证的地址返回 404
+        match self.client.get_json(&api_url).await? {
+            Some(resp) => Ok(parse_contract(address, &resp)),
+            None => Ok(ContractSource::new(address)),
+        }
+    }
+
+    /// 未验证的合约也会返回 `creation_bytecode`
+    async fn fetch_creation(&self, address: &str) -> Result<Option<Creation>> {
+        let api_url = format!("{}/api/v2/smart-contracts/{}", self.base_url, address);
+        let Some(resp) = self.client.get_json(&api_url).await? else {
+            return Ok(None);
+        };
+        Ok(normalize_hex(resp["creation_bytecode"].as_str())
+            .map(|bytecode| Creation { tx_hash: None, bytecode: Some(bytecode) }))
+    }
+}
+
+
+/// 解析合约信息，没有 `source_code` 时表示合约未验证，返回空的 `ContractSource`
+fn parse_contract(address: &str, resp: &Value) -> ContractSource {
+    let mut source = ContractSource::new(address);
+    let Some(main_sol) = resp["source_code"].as_str() else {
+        return source;
+    };
+    source.add_file(resp["file_path"].as_str().unwrap_or("Error: No main file path"), main_sol);
+    if let Some(additional_sources) = resp["additional_sources"].as_array() {
+        for item in additional_sources {
+            let code = item["source_code"].as_str()
+                .unwrap_or("Error: No additional_sources code");
+            let path = item["file_path"].as_str()
+                .unwrap_or("Error: No additional_sources file path");
+            source.add_file(path, code);
+        }
+    }
+    source.metadata = parse_metadata(resp);
+    source
+}
+
+
+fn parse_metadata(resp: &Value) -> ContractMetadata {
+    let string = |key: &str| non_empty(resp[key].as_str());
+    let object = |key: &str| Some(resp[key].clone())
+        .filter(|v| !v.is_null());
+    // external_libraries 为 [{"name": ..., "address_hash": ...}]
+    let libraries = resp["external_libraries"].as_array()
+        .filter(|libs| !libs.is_empty())
+        .map(|libs| {
+            let map: Map<String, Value> = libs.iter()
+                .filter_map(|lib| Some((lib["name"].as_str()?.to_string(), lib["address_hash"].clone())))
+                .collect();
+            Value::Object(map)
+        });
+    // 新版本为 implementations: [{"address": ...}]，旧版本为 implementation_address
+    let implementation = resp["implementations"].as_array()
+        .and_then(|impls| impls.first())
+        .and_then(|i| i["address"].as_str().or(i["address_hash"].as_str()))
+        .or(resp["implementation_address"].as_str());
+    let proxy_type = string("proxy_type").filter(|t| t != "unknown");
+    ContractMetadata {
+        contract_name: string("name"),
+        compiler_version: normalize_compiler_version(resp["compiler_version"].as_str()),
+        optimization_enabled: resp["optimization_enabled"].as_bool(),
+        optimization_runs: resp["optimization_runs"].as_u64(),
+        evm_version: string("evm_version").map(|v| v.to_lowercase()),
+        constructor_args: normalize_hex(resp["constructor_args"].as_str()),
+        libraries,
+        license_type: normalize_license(resp["license_type"].as_str()),
+        proxy: Some(proxy_type.is_some() || implementation.is_some()),
+        implementation: non_empty(implementation),
+        compiler_settings: object("compiler_settings"),
+        abi: object("abi"),
+        ..ContractMetadata::default()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_contract() {
+        let resp = json!({
+            "name": "Token",
+            "file_path": "contracts/Token.sol",
+            "source_code": "contract Token {}",
+            "additional_sources": [{"file_path": "contracts/Lib.sol", "source_code": "library Lib {}"}],
+            "compiler_version": "v0.8.19+commit.7dd6d404",
+            "optimization_enabled": true,
+            "optimization_runs": 200,
+            "evm_version": "paris",
+            "constructor_args": "",
+            "external_libraries": [{"name": "Lib", "address_hash": "0x0000000000000000000000000000000000000001"}],
+            "compiler_settings": {"optimizer": {"enabled": true, "runs": 200}},
+            "abi": [{"type": "constructor", "inputs": []}],
+            "license_type": "mit",
+            "proxy_type": "eip1967",
+            "implementations": [{"address": "0x43506849D7C04F9138D1A2050bbF3A0c054402dd", "name": "TokenV2"}]
+        });
+        let source = parse_contract("0x0", &resp);
+        let paths: Vec<_> = source.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["contracts/Token.sol", "contracts/Lib.sol"]);
+
+        let metadata = source.metadata;
+        assert_eq!(metadata.contract_name.as_deref(), Some("Token"));
+        assert_eq!(metadata.optimization_runs, Some(200));
+        assert_eq!(metadata.compiler_version.as_deref(), Some("0.8.19+commit.7dd6d404"));
+        // 与 Etherscan 的 `MIT` 相同
+        assert_eq!(metadata.license_type.as_deref(), Some("MIT"));
+        let gpl = parse_metadata(&json!({"license_type": "gnu_gpl_v3"}));
+        assert_eq!(gpl.license_type.as_deref(), Some("GPL-3.0"));
+        assert_eq!(metadata.proxy, Some(true));
+        assert_eq!(metadata.implementation.as_deref(), Some("0x43506849D7C04F9138D1A2050bbF3A0c054402dd"));
+        assert_eq!(metadata.constructor_args, None);
+        assert_eq!(metadata.libraries, Some(json!({"Lib": "0x0000000000000000000000000000000000000001"})));
+        assert!(metadata.abi.unwrap().is_array());
+
+        assert!(parse_contract("0x0", &json!({"is_verified": false})).files.is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{Map, Value};
+
+use super::{add_sources, non_empty, normalize_compiler_version, normalize_hex, normalize_license, ContractMetadata, ContractSource, Creation, SourceProvider};
+use crate::error::RetrieveError;
+use crate::http::Client;
+
+
+/// Etherscan 风格的 `getsourcecode` 接口
+///
+/// 适用于 Etherscan V2 支持的所有链，以及 routescan、btrscan 等兼容 Etherscan 的浏览器
+pub struct EtherscanProvider {
+    name: String,
+    api_url: String,
+    chain_id: Option<u32>,
+    api_key: Option<String>,
+    api_key_param: String,
+    client: Client,
+}
+
+impl EtherscanProvider {
+    /// `api_url` 为接口地址，例如 `https://api.etherscan.io/v2/api`，
+    /// `chain_id` 仅 Etherscan V2 这类多链接口需要，`api_key_param` 为 API key 的参数名
+    pub fn new(
+        name: &str,
+        api_url: &str,
+        chain_id: Option<u32>,
+        api_key: Option<String>,
+        api_key_param: &str,
+        client: Client,
+    ) -> Self {
+        EtherscanProvider {
+            name: name.to_string(),
+            api_url: api_url.to_string(),
+            chain_id,
+            api_key,
+            api_key_param: api_key_param.to_string(),
+            client,
+        }
+    }
+
+    /// `params` 为 `module=...&action=...` 等查询参数
+    fn request_url(&self, params: &str) -> String {
+        let mut url = format!("{}?{}", self.api_url, params);
+        if let Some(chain_id) = self.chain_id {
+            url.push_str(&format!("&chainid={}", chain_id));
+        }
+        if let Some(api_key) = &self.api_key {
+            url.push_str(&format!("&{}={}", self.api_key_param, api_key));
+        }
+        url
+    }
+}
+
+
+/// 解析 `result` 中的一项，`SourceCode` 可能是以下几种格式之一：
+///
+/// - 单文件（或 flatten 后的）源码
+/// - `{...}` 包裹的 JSON，内容为 standard-json 或者 `{文件名: {content}}`
+/// - `{{...}}` 包裹的 standard-json
+fn parse_item(source: &mut ContractSource, item: &Value) -> Result<()> {
+    let code = item["SourceCode"].as_str().unwrap_or("");
+    let trimmed = code.trim();
+    if trimmed.is_empty() {
+        println!("Empty source code for {}", source.address);
+        return Ok(());
+    }
+    if trimmed.starts_with("{{") && trimmed.ends_with("}}") {
+        let input: Value = serde_json::from_str(&trimmed[1..trimmed.len() - 1])
+            .map_err(|e| RetrieveError::ParseError(format!("Invalid standard-json SourceCode: {}", e)))?;
+        add_sources(source, &input);
+        source.metadata.compiler_settings = input.get("settings").cloned();
+        source.input = Some(input);
+        return Ok(());
+    }
+    if trimmed.starts_with('{') {
+        if let Ok(input) = serde_json::from_str::<Value>(trimmed) {
+            if input.get("sources").is_some() {
+                add_sources(source, &input);
+                source.metadata.compiler_settings = input.get("settings").cloned();
+                source.input = Some(input);
+            } else if let Some(files) = input.as_object() {
+                for (p, c) in files {
+                    source.add_file(p, c["content"].as_str().unwrap_or("Error: No content"));
+                }
+            }
+            return Ok(());
+        }
+    }
+    let contract_name = item["ContractName"].as_str().unwrap_or("Error: No contract name");
+    let extension = match item["CompilerVersion"].as_str() {
+        Some(version) if version.starts_with("vyper") => "vy",
+        _ => "sol",
+    };
+    source.add_file(&format!("{}.{}", contract_name, extension), code);
+    Ok(())
+}
+
+
+/// 解析 `result` 中一项的编译信息，Etherscan 的所有字段都是字符串
+fn parse_metadata(item: &Value) -> ContractMetadata {
+    let string = |key: &str| non_empty(item[key].as_str());
+    // 未验证或者没有 ABI 时为提示文本
+    let abi = item["ABI"].as_str()
+        .and_then(|abi| serde_json::from_str::<Value>(abi).ok())
+        .filter(Value::is_array);
+    ContractMetadata {
+        contract_name: string("ContractName"),
+        compiler_version: normalize_compiler_version(item["CompilerVersion"].as_str()),
+        optimization_enabled: string("OptimizationUsed").map(|v| v == "1"),
+        optimization_runs: string("Runs").and_then(|v| v.parse().ok()),
+        evm_version: string("EVMVersion").map(|v| v.to_lowercase()),
+        constructor_args: normalize_hex(item["ConstructorArguments"].as_str()),
+        libraries: string("Library").and_then(|libs| parse_libraries(&libs)),
+        license_type: normalize_license(item["LicenseType"].as_str()),
+        proxy: string("Proxy").map(|v| v == "1"),
+        implementation: string("Implementation"),
+        abi,
+        ..ContractMetadata::default()
+    }
+}
+
+
+/// `Library` 的格式为 `Name:address;Name2:address2`，地址可能没有 `0x` 前缀
+fn parse_libraries(libraries: &str) -> Option<Value> {
+    let map: Map<String, Value> = libraries.split(';')
+        .filter_map(|lib| lib.split_once(':'))
+        .map(|(name, address)| {
+            let address = address.trim();
+            let address = if address.starts_with("0x") { address.to_string() } else { format!("0x{}", address) };
+            (name.trim().to_string(), Value::String(address))
+        })
+        .collect();
+    Some(Value::Object(map)).filter(|libs| libs.as_object().is_some_and(|l| !l.is_empty()))
+}
+
+
+#[async_trait]
+impl SourceProvider for EtherscanProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self, address: &str) -> Result<ContractSource> {
+        let mut source = ContractSource::new(address);
+        let url = self.request_url(&format!("module=contract&action=getsourcecode&address={}", address));
+        let Some(resp) = self.client.get_json(&url).await? else {
+            return Ok(source);
+        };
+        // 大部分浏览器返回字符串 "1"，btrscan 返回数字 1
+        if resp["status"] != "1" && resp["status"] != 1 {
+            let message = resp["result"].as_str()
+                .or(resp["message"].as_str())
+                .unwrap_or("Unknown error");
+            return match RetrieveError::from_api_message(message) {
+                RetrieveError::NotVerified(_) => Ok(source),
+                e => Err(e.into()),
+            };
+        }
+        let result = resp["result"].as_array()
+            .ok_or_else(|| RetrieveError::ParseError("Result is not an array".to_string()))?;
+        if let Some(item) = result.first() {
+            source.metadata = parse_metadata(item);
+        }
+        for item in result {
+            parse_item(&mut source, item)?;
+        }
+        Ok(source)
+    }
+
+    /// `getcontractcreation` 接口，较新的 Etherscan 会直接返回 `creationBytecode`
+    async fn fetch_creation(&self, address: &str) -> Result<Option<Creation>> {
+        let url = self.request_url(&format!("module=contract&action=getcontractcreation&contractaddresses={}", address));
+        let Some(resp) = self.client.get_json(&url).await? else {
+            return Ok(None);
+        };
+        Ok(parse_creation(&resp))
+    }
+}
+
+
+/// 不支持该接口或者地址不是合约时返回 `None`
+fn parse_creation(resp: &Value) -> Option<Creation> {
+    if resp["status"] != "1" && resp["status"] != 1 {
+        return None;
+    }
+    let item = resp["result"].as_array()?.first()?;
+    let creation = Creation {
+        tx_hash: non_empty(item["txHash"].as_str()),
+        bytecode: normalize_hex(item["creationBytecode"].as_str()),
+    };
+    (creation.tx_hash.is_some() || creation.bytecode.is_some()).then_some(creation)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(item: Value) -> Vec<(String, String)> {
+        let mut source = ContractSource::new("0x0");
+        parse_item(&mut source, &item).unwrap();
+        source.files.into_iter().map(|f| (f.path, f.content)).collect()
+    }
+
+    #[test]
+    fn test_parse_source_code_encodings() {
+        let plain = parse(json!({"SourceCode": "contract A {}", "ContractName": "A"}));
+        assert_eq!(plain, vec![("A.sol".to_string(), "contract A {}".to_string())]);
+
+        let vyper = parse(json!({"SourceCode": "# @version 0.3.7", "ContractName": "B", "CompilerVersion": "vyper:0.3.7"}));
+        assert_eq!(vyper[0].0, "B.vy");
+
+        let files = parse(json!({"SourceCode": r#"{"A.sol": {"content": "contract A {}"}}"#}));
+        assert_eq!(files, vec![("A.sol".to_string(), "contract A {}".to_string())]);
+
+        let single = parse(json!({"SourceCode": r#"{"language": "Solidity", "sources": {"src/A.sol": {"content": "a"}}}"#}));
+        assert_eq!(single, vec![("src/A.sol".to_string(), "a".to_string())]);
+
+        let double = parse(json!({"SourceCode": r#"{{"language": "Solidity", "sources": {"src/A.sol": {"content": "a{{}}"}}}}"#}));
+        assert_eq!(double, vec![("src/A.sol".to_string(), "a{{}}".to_string())]);
+
+        assert!(parse(json!({"SourceCode": ""})).is_empty());
+    }
+
+    #[test]
+    fn test_parse_creation() {
+        let creation = parse_creation(&json!({"status": "1", "message": "OK", "result": [{
+            "contractAddress": "0x01",
+            "contractCreator": "0x02",
+            "txHash": "0xabc",
+            "creationBytecode": "0x6080"
+        }]})).unwrap();
+        assert_eq!(creation.tx_hash.as_deref(), Some("0xabc"));
+        assert_eq!(creation.bytecode.as_deref(), Some("0x6080"));
+        assert_eq!(parse_creation(&json!({"status": "0", "message": "No data found", "result": []})), None);
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let metadata = parse_metadata(&json!({
+            "ContractName": "Token",
+            "CompilerVersion": "v0.8.19+commit.7dd6d404",
+            "OptimizationUsed": "1",
+            "Runs": "200",
+            "EVMVersion": "Default",
+            "ConstructorArguments": "000000000000000000000000ABCD",
+            "Library": "SafeMath:3c4d0b0e4d5f8a1a7c4c1b8d2e5f6a7b8c9d0e1f",
+            "LicenseType": "MIT",
+            "Proxy": "1",
+            "Implementation": "0x43506849D7C04F9138D1A2050bbF3A0c054402dd",
+            "ABI": "[{\"type\":\"constructor\",\"inputs\":[]}]",
+            "SwarmSource": ""
+        }));
+        assert_eq!(metadata.compiler_version.as_deref(), Some("0.8.19+commit.7dd6d404"));
+        assert_eq!(metadata.optimization_enabled, Some(true));
+        assert_eq!(metadata.optimization_runs, Some(200));
+        assert_eq!(metadata.evm_version, None);
+        assert_eq!(metadata.constructor_args.as_deref(), Some("0x000000000000000000000000abcd"));
+        assert_eq!(metadata.libraries, Some(json!({"SafeMath": "0x3c4d0b0e4d5f8a1a7c4c1b8d2e5f6a7b8c9d0e1f"})));
+        assert_eq!(metadata.license_type.as_deref(), Some("MIT"));
+        assert_eq!(metadata.proxy, Some(true));
+        assert_eq!(metadata.implementation.as_deref(), Some("0x43506849D7C04F9138D1A2050bbF3A0c054402dd"));
+        assert!(metadata.abi.unwrap().is_array());
+
+        let unverified = parse_metadata(&json!({"ABI": "Contract source code not verified", "LicenseType": "None", "Library": ""}));
+        assert_eq!(unverified.abi, None);
+        assert_eq!(unverified.license_type, None);
+        let gpl = parse_metadata(&json!({"LicenseType": "GNU GPLv3"}));
+        assert_eq!(gpl.license_type.as_deref(), Some("GPL-3.0"));
+        assert_eq!(unverified.libraries, None);
+    }
+}
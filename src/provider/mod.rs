@@ -0,0 +1,354 @@
+mod blockscout;
+mod etherscan;
+mod sourcify;
+mod standard_json;
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+
+pub use blockscout::BlockscoutProvider;
+pub use etherscan::EtherscanProvider;
+pub use sourcify::{SourcifyProvider, SOURCIFY_URL};
+
+use crate::chain::{Chain, Explorer, ExplorerKind};
+use crate::config::ApiKeys;
+use crate::error::RetrieveError;
+use crate::http::Client;
+
+
+/// 单个源码文件，`path` 为相对于合约目录的路径
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// 合约的编译信息，数据源没有提供的字段为 `None`
+///
+/// 各个数据源的字段统一为相同的格式：`compiler_version` 不带 `v` 前缀，
+/// `constructor_args` 为带 `0x` 前缀的小写十六进制，`evm_version` 为小写
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ContractMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimization_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub optimization_runs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evm_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constructor_args: Option<String>,
+    /// 库名称到地址的映射
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub libraries: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler_settings: Option<Value>,
+    /// SPDX 标识符，例如 `MIT`、`GPL-3.0`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_type: Option<String>,
+    /// 区块浏览器是否将合约识别为代理合约
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<bool>,
+    /// 代理合约的实现合约地址
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub implementation: Option<String>,
+    /// 识别代理合约的方式：`explorer`、`eip1967`、`eip1822` 或 `beacon`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_type: Option<String>,
+    /// 实现合约的源码目录，相对于当前合约目录
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub implementation_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abi: Option<Value>,
+    /// Sourcify 的匹配类型：`full` 或 `partial`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_type: Option<String>,
+    /// 实际提供源码的数据源
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// 在此之前尝试失败的数据源
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_providers: Vec<ProviderFailure>,
+    /// 保存时被改写的源码路径，保存路径到数据源原始路径的映射
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub original_paths: BTreeMap<String, String>,
+}
+
+/// 某个数据源没有返回源码的原因
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProviderFailure {
+    pub provider: String,
+    pub reason: String,
+}
+
+impl ContractMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == ContractMetadata::default()
+    }
+}
+
+
+/// 从某个数据源获取到的合约源码
+#[derive(Debug, Clone, Default)]
+pub struct ContractSource {
+    pub address: String,
+    pub files: Vec<SourceFile>,
+    pub metadata: ContractMetadata,
+    /// 数据源提供的原始 standard-json 输入
+    pub input: Option<Value>,
+}
+
+impl ContractSource {
+    pub fn new(address: &str) -> Self {
+        ContractSource {
+            address: address.to_string(),
+            files: Vec::new(),
+            metadata: ContractMetadata::default(),
+            input: None,
+        }
+    }
+
+    pub fn add_file(&mut self, path: &str, content: &str) {
+        self.files.push(SourceFile {
+            path: path.to_string(),
+            content: content.to_string(),
+        });
+    }
+
+    /// 可以直接用于 `solc --standard-json` 的输入，没有原始输入时根据源码和编译信息生成
+    pub fn standard_json_input(&self) -> Option<Value> {
+        standard_json::build(self)
+    }
+}
+
+
+/// 智能合约源码数据源
+///
+/// 每个区块浏览器只需要负责把接口返回的数据转换为 `ContractSource`，
+/// 保存到磁盘由 `get_code` 统一处理。
+#[async_trait]
+pub trait SourceProvider: Send + Sync {
+    /// 数据源名称，用于日志输出
+    fn name(&self) -> &str;
+
+    /// 获取 `address` 上已验证合约的源码，未验证时返回空的 `ContractSource`
+    async fn fetch(&self, address: &str) -> Result<ContractSource>;
+
+    /// 获取合约的创建信息，不支持时返回 `None`
+    async fn fetch_creation(&self, _address: &str) -> Result<Option<Creation>> {
+        Ok(None)
+    }
+}
+
+
+/// 合约的创建交易和创建字节码（包含构造函数参数）
+#[derive(Debug, Clone, PartialEq)]
+pub struct Creation {
+    pub tx_hash: Option<String>,
+    pub bytecode: Option<String>,
+}
+
+
+/// 依次尝试链上配置的数据源，返回第一个获取到的源码
+///
+/// 所有数据源都失败时，如果有数据源出错则返回第一个错误，否则返回 `NotVerified`
+pub async fn fetch_source(chain: &Chain, keys: &ApiKeys, address: &str) -> Result<ContractSource, RetrieveError> {
+    let mut failures = Vec::new();
+    let mut first_error = None;
+    for explorer in &chain.explorers {
+        let (name, result) = match create_provider(chain, explorer, keys) {
+            Ok(provider) => (provider.name().to_string(), provider.fetch(address).await),
+            Err(e) => (explorer.name.clone(), Err(e)),
+        };
+        let reason = match result.map_err(RetrieveError::from) {
+            Ok(mut source) if !source.files.is_empty() => {
+                source.metadata.provider = Some(name);
+                source.metadata.failed_providers = failures;
+                return Ok(source);
+            }
+            Ok(_) | Err(RetrieveError::NotVerified(_)) => "not verified".to_string(),
+            Err(e) => {
+                let reason = e.to_string();
+                first_error.get_or_insert(e);
+                reason
+            }
+        };
+        println!("{} failed for {}: {}", name, address, reason);
+        failures.push(ProviderFailure { provider: name, reason });
+    }
+    Err(first_error.unwrap_or_else(|| RetrieveError::NotVerified(address.to_string())))
+}
+
+
+/// 依次尝试链上配置的数据源获取合约的创建信息，都不支持时返回 `None`
+pub async fn fetch_creation(chain: &Chain, keys: &ApiKeys, address: &str) -> Option<Creation> {
+    for explorer in &chain.explorers {
+        let result = match create_provider(chain, explorer, keys) {
+            Ok(provider) => provider.fetch_creation(address).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(Some(creation)) => return Some(creation),
+            Ok(None) => {}
+            Err(e) => println!("{} failed to get the creation of {}: {:#}", explorer.name, address, e),
+        }
+    }
+    None
+}
+
+
+/// 使用指定的区块浏览器为 `chain` 创建数据源，需要 API key 的数据源在缺少 key 时返回错误
+///
+/// 同一个 `api_url` 的数据源共享限流器，例如 Etherscan V2 支持的所有链
+pub fn create_provider(chain: &Chain, explorer: &Explorer, keys: &ApiKeys) -> Result<Box<dyn SourceProvider>> {
+    let client = Client::new(&explorer.api_url, explorer.rate_limit);
+    let provider: Box<dyn SourceProvider> = match explorer.kind {
+        ExplorerKind::Etherscan | ExplorerKind::Routescan => {
+            let api_key = match &explorer.api_key {
+                Some(name) => Some(keys.require(name, &chain.name)?),
+                None => None,
+            };
+            Box::new(EtherscanProvider::new(
+                &explorer.name,
+                &explorer.api_url,
+                explorer.chain_id,
+                api_key,
+                &explorer.api_key_param,
+                client,
+            ))
+        }
+        ExplorerKind::Blockscout => Box::new(BlockscoutProvider::new(&explorer.name, &explorer.api_url, client)),
+        ExplorerKind::Sourcify => Box::new(SourcifyProvider::new(&explorer.name, &explorer.api_url, chain.chain_id, client)),
+    };
+    Ok(provider)
+}
+
+
+/// 去掉空字符串和数据源表示“没有”的占位值
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value.map(str::trim)
+        .filter(|v| !v.is_empty() && !v.eq_ignore_ascii_case("none") && !v.eq_ignore_ascii_case("default"))
+        .map(str::to_string)
+}
+
+
+/// `v0.8.19+commit.7dd6d404` 统一为 `0.8.19+commit.7dd6d404`，Vyper 的 `vyper:0.3.7` 保持不变
+fn normalize_compiler_version(version: Option<&str>) -> Option<String> {
+    let version = non_empty(version)?;
+    match version.strip_prefix('v') {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => Some(rest.to_string()),
+        _ => Some(version),
+    }
+}
+
+
+/// Etherscan（`GNU GPLv3`）和 Blockscout（`gnu_gpl_v3`）的 license 名称统一为 SPDX 标识符（`GPL-3.0`），
+/// 无法识别的名称保持不变
+fn normalize_license(value: Option<&str>) -> Option<String> {
+    let value = non_empty(value)?;
+    let key = value.chars().filter(char::is_ascii_alphanumeric).collect::<String>().to_ascii_lowercase();
+    let spdx = match key.as_str() {
+        "unlicense" => "Unlicense",
+        "unlicensed" => "UNLICENSED",
+        "mit" => "MIT",
+        "gnugplv2" => "GPL-2.0",
+        "gnugplv3" => "GPL-3.0",
+        "gnulgplv21" => "LGPL-2.1",
+        "gnulgplv3" => "LGPL-3.0",
+        "gnuagplv3" => "AGPL-3.0",
+        "bsd2clause" => "BSD-2-Clause",
+        "bsd3clause" => "BSD-3-Clause",
+        "mpl20" => "MPL-2.0",
+        "osl30" => "OSL-3.0",
+        "apache20" => "Apache-2.0",
+        "bsl11" => "BUSL-1.1",
+        _ => return Some(value),
+    };
+    Some(spdx.to_string())
+}
+
+
+/// 十六进制字符串统一为小写并带 `0x` 前缀
+fn normalize_hex(value: Option<&str>) -> Option<String> {
+    let value = non_empty(value)?;
+    let hex = value.strip_prefix("0x").unwrap_or(&value);
+    Some(format!("0x{}", hex.to_lowercase()))
+}
+
+
+/// 将 standard-json 中的 `sources` 字段加入到 `source` 中
+fn add_sources(source: &mut ContractSource, input: &Value) {
+    if let Some(sources) = input["sources"].as_object() {
+        for (p, c) in sources {
+            let code = c["content"].as_str().unwrap_or("Error: No content");
+            source.add_file(p, code);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock_registry;
+    use mockito::{mock, Matcher};
+    use serde_json::json;
+
+    const ADDRESS: &str = "0x00000000219ab540356cBB839Cbe05303d7705Fa";
+
+    #[tokio::test]
+    async fn test_fetch_source_falls_back() {
+        let (registry, keys) = mock_registry(r#"
+            [sourcify]
+            api_url = "{url}"
+
+            [chains.eth]
+            explorers = [{ provider = "etherscan", api_url = "{url}/api" }]
+        "#);
+
+        let _etherscan = mock("GET", Matcher::Regex(r"^/api\?".to_string()))
+            .with_body(r#"{"status": "0", "message": "NOTOK", "result": "Contract source code not verified"}"#)
+            .create();
+        let _sourcify = mock("GET", format!("/files/any/1/{}", ADDRESS).as_str())
+            .with_body(json!({
+                "status": "full",
+                "files": [{"name": "A.sol", "path": format!("/contracts/full_match/1/{}/sources/A.sol", ADDRESS), "content": "contract A {}"}]
+            }).to_string())
+            .create();
+
+        let chain = registry.resolve("eth").unwrap();
+        let source = fetch_source(chain, &keys, ADDRESS).await.unwrap();
+        assert_eq!(source.files[0].path, "A.sol");
+        assert_eq!(source.metadata.provider.as_deref(), Some("sourcify"));
+        assert_eq!(source.metadata.failed_providers, vec![ProviderFailure {
+            provider: "eth etherscan".to_string(),
+            reason: "not verified".to_string(),
+        }]);
+    }
+
+    #[tokio::test]
+    async fn test_merlin_api_key_param() {
+        let (registry, keys) = mock_registry(r#"
+            [sourcify]
+            fallback = false
+
+            [chains.merlin]
+            api_url = "{url}/merlin-api"
+        "#);
+        let merlin = mock("GET", "/merlin-api")
+            .match_query(Matcher::Exact(format!("module=contract&action=getsourcecode&address={}&api_key=test", ADDRESS)))
+            .with_body(json!({"status": "1", "message": "OK", "result": [{"SourceCode": "contract A {}", "ContractName": "A"}]}).to_string())
+            .expect(1)
+            .create();
+        let source = fetch_source(registry.resolve("merlin").unwrap(), &keys, ADDRESS).await.unwrap();
+        assert_eq!(source.files[0].path, "A.sol");
+        merlin.assert();
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+
+use super::{normalize_compiler_version, normalize_hex, normalize_license, ContractMetadata, ContractSource, SourceProvider};
+use crate::http::Client;
+
+/// Sourcify 官方服务地址
+pub const SOURCIFY_URL: &str = "https://sourcify.dev/server";
+
+
+/// Sourcify 的 `/files/any/{chainId}/{address}` 接口，同时支持 full match 和 partial match
+pub struct SourcifyProvider {
+    name: String,
+    base_url: String,
+    chain_id: u32,
+    client: Client,
+}
+
+impl SourcifyProvider {
+    pub fn new(name: &str, base_url: &str, chain_id: u32, client: Client) -> Self {
+        SourcifyProvider {
+            name: name.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            chain_id,
+            client,
+        }
+    }
+}
+
+#[async_trait]
+impl SourceProvider for SourcifyProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch(&self, address: &str) -> Result<ContractSource> {
+        let api_url = format!("{}/files/any/{}/{}", self.base_url, self.chain_id, address);
+        // 未验证的合约返回 404
+        match self.client.get_json(&api_url).await? {
+            Some(resp) => parse_files(address, &resp),
+            None => Ok(ContractSource::new(address)),
+        }
+    }
+}
+
+
+/// 解析 `files/any` 的返回值，`status` 为 `full` 或 `partial`
+fn parse_files(address: &str, resp: &Value) -> Result<ContractSource> {
+    let files = resp["files"].as_array().ok_or(anyhow!("Files is not an array"))?;
+    let mut source = ContractSource::new(address);
+    let mut constructor_args = None;
+    for file in files {
+        let content = file["content"].as_str().unwrap_or("");
+        let path = relative_path(address, file);
+        if let Some(path) = path.strip_prefix("sources/") {
+            source.add_file(path, content);
+        } else if path == "metadata.json" {
+            source.metadata = parse_metadata(&serde_json::from_str(content)?);
+        } else if path == "constructor-args.txt" {
+            constructor_args = normalize_hex(Some(content));
+        }
+    }
+    source.metadata.constructor_args = constructor_args;
+    source.metadata.match_type = resp["status"].as_str().map(str::to_string);
+    Ok(source)
+}
+
+
+/// 文件在合约目录中的相对路径
+///
+/// `path` 形如 `/home/data/repository/contracts/full_match/1/0x.../sources/contracts/A.sol`
+fn relative_path(address: &str, file: &Value) -> String {
+    let name = file["name"].as_str().unwrap_or("");
+    let path = file["path"].as_str().unwrap_or(name);
+    // 只转换 ASCII 字符，保证下标在原字符串中同样有效
+    let marker = format!("/{}/", address.to_ascii_lowercase());
+    match path.to_ascii_lowercase().find(&marker) {
+        Some(i) => path[i + marker.len()..].to_string(),
+        None => name.to_string(),
+    }
+}
+
+
+/// 从 solc 的 metadata.json 中提取编译信息
+fn parse_metadata(metadata: &Value) -> ContractMetadata {
+    let settings = &metadata["settings"];
+    let libraries = Some(settings["libraries"].clone())
+        .filter(|libs| libs.as_object().is_some_and(|libs| !libs.is_empty()));
+    ContractMetadata {
+        contract_name: settings["compilationTarget"].as_object()
+            .and_then(|target| target.values().next())
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        compiler_version: normalize_compiler_version(metadata["compiler"]["version"].as_str()),
+        optimization_enabled: settings["optimizer"]["enabled"].as_bool(),
+        optimization_runs: settings["optimizer"]["runs"].as_u64(),
+        evm_version: settings["evmVersion"].as_str().map(str::to_lowercase),
+        // 主合约的 SPDX license
+        license_type: settings["compilationTarget"].as_object()
+            .and_then(|target| target.keys().next())
+            .and_then(|path| normalize_license(metadata["sources"][path]["license"].as_str())),
+        libraries,
+        compiler_settings: Some(settings.clone()).filter(|s| !s.is_null()),
+        abi: Some(metadata["output"]["abi"].clone()).filter(|abi| !abi.is_null()),
+        ..ContractMetadata::default()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::mock;
+    use serde_json::json;
+
+    const ADDRESS: &str = "0x00000000219ab540356cBB839Cbe05303d7705Fa";
+
+    fn files_body(status: &str) -> String {
+        let dir = format!("/home/data/repository/contracts/{}_match/1/{}", status, ADDRESS);
+        let metadata = json!({
+            "compiler": {"version": "0.6.11+commit.5ef660b1"},
+            "settings": {
+                "compilationTarget": {"deposit_contract.sol": "DepositContract"},
+                "evmVersion": "istanbul",
+                "libraries": {},
+                "optimizer": {"enabled": true, "runs": 5000000}
+            },
+            "output": {"abi": []}
+        });
+        json!({
+            "status": status,
+            "files": [
+                {"name": "metadata.json", "path": format!("{}/metadata.json", dir), "content": metadata.to_string()},
+                {"name": "deposit_contract.sol", "path": format!("{}/sources/deposit_contract.sol", dir), "content": "contract DepositContract {}"},
+                {"name": "constructor-args.txt", "path": format!("{}/constructor-args.txt", dir), "content": "0x"}
+            ]
+        }).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_full_and_partial_match() {
+        let provider = SourcifyProvider::new("sourcify", &mockito::server_url(), 1, Client::new("test:sourcify", 100.0));
+        for status in ["full", "partial"] {
+            let _m = mock("GET", format!("/files/any/1/{}", ADDRESS).as_str())
+                .with_status(200)
+                .with_header("content-type", "application/json")
+                .with_body(files_body(status))
+                .create();
+            let source = provider.fetch(ADDRESS).await.unwrap();
+            assert_eq!(source.files.len(), 1);
+            assert_eq!(source.files[0].path, "deposit_contract.sol");
+            assert_eq!(source.metadata.match_type.as_deref(), Some(status));
+            assert_eq!(source.metadata.contract_name.as_deref(), Some("DepositContract"));
+            assert_eq!(source.metadata.optimization_runs, Some(5000000));
+            assert_eq!(source.metadata.libraries, None);
+            assert_eq!(source.metadata.constructor_args.as_deref(), Some("0x"));
+        }
+    }
+
+    #[test]
+    fn test_relative_path_with_unicode() {
+        // `İ` 转小写后字节长度会变化
+        let file = json!({"name": "A.sol", "path": format!("/İİ/full_match/1/{}/sources/İ/A.sol", ADDRESS)});
+        assert_eq!(relative_path(ADDRESS, &file), "sources/İ/A.sol");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_not_found() {
+        let _m = mock("GET", format!("/files/any/1/{}", ADDRESS).as_str())
+            .with_status(404)
+            .with_body(r#"{"error": "Files have not been found!"}"#)
+            .create();
+        let provider = SourcifyProvider::new("sourcify", &mockito::server_url(), 1, Client::new("test:sourcify", 100.0));
+        assert!(provider.fetch(ADDRESS).await.unwrap().files.is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+use serde_json::{json, Map, Value};
+
+use super::ContractSource;
+
+
+/// `solc --standard-json` 的输入，优先使用数据源提供的原始输入，
+/// 否则根据源码文件和编译信息生成。没有源码文件时返回 `None`
+pub fn build(source: &ContractSource) -> Option<Value> {
+    if let Some(input) = &source.input {
+        return Some(input.clone());
+    }
+    if source.files.is_empty() {
+        return None;
+    }
+    let sources: Map<String, Value> = source.files.iter()
+        .map(|f| (f.path.clone(), json!({"content": f.content})))
+        .collect();
+    let vyper = is_vyper(source);
+    let settings = if vyper { vyper_settings(source) } else { solidity_settings(source) };
+    Some(json!({
+        "language": if vyper { "Vyper" } else { "Solidity" },
+        "sources": sources,
+        "settings": settings,
+    }))
+}
+
+
+fn is_vyper(source: &ContractSource) -> bool {
+    match &source.metadata.compiler_version {
+        Some(version) => version.starts_with("vyper"),
+        None => source.files.iter().all(|f| f.path.ends_with(".vy")),
+    }
+}
+
+
+fn solidity_settings(source: &ContractSource) -> Value {
+    let metadata = &source.metadata;
+    // Blockscout 返回的 compiler_settings 就是 standard-json 的 settings，
+    // Sourcify 的是 metadata.json 中的 settings，需要去掉 compilationTarget 并转换 libraries
+    let mut settings = match &metadata.compiler_settings {
+        Some(Value::Object(settings)) => settings.clone(),
+        _ => Map::new(),
+    };
+    settings.remove("compilationTarget");
+    if !settings.contains_key("optimizer") {
+        if let Some(enabled) = metadata.optimization_enabled {
+            settings.insert("optimizer".to_string(), json!({
+                "enabled": enabled,
+                "runs": metadata.optimization_runs.unwrap_or(200),
+            }));
+        }
+    }
+    if let Some(evm_version) = &metadata.evm_version {
+        settings.entry("evmVersion").or_insert_with(|| json!(evm_version));
+    }
+    let libraries = settings.remove("libraries")
+        .or_else(|| metadata.libraries.clone())
+        .map(|libs| group_libraries(&libs, source))
+        .filter(|libs| !libs.is_empty());
+    if let Some(libraries) = libraries {
+        settings.insert("libraries".to_string(), Value::Object(libraries));
+    }
+    settings.entry("outputSelection").or_insert_with(|| json!({
+        "*": {
+            "*": ["abi", "evm.bytecode", "evm.deployedBytecode", "evm.methodIdentifiers", "metadata"],
+            "": ["ast"],
+        }
+    }));
+    Value::Object(settings)
+}
+
+
+fn vyper_settings(source: &ContractSource) -> Value {
+    let mut settings = Map::new();
+    if let Some(evm_version) = &source.metadata.evm_version {
+        settings.insert("evmVersion".to_string(), json!(evm_version));
+    }
+    settings.insert("outputSelection".to_string(), json!({"*": ["abi", "evm.bytecode", "evm.deployedBytecode"]}));
+    Value::Object(settings)
+}
+
+
+/// standard-json 的 `libraries` 格式为 `{文件: {库名称: 地址}}`
+///
+/// 已经是这种格式时保持不变；`{"文件:库名称": 地址}` 按文件分组；
+/// 只有 `{库名称: 地址}` 时无法知道库所在的文件，单文件合约使用该文件，否则使用空文件名
+fn group_libraries(libraries: &Value, source: &ContractSource) -> Map<String, Value> {
+    let Some(libraries) = libraries.as_object() else {
+        return Map::new();
+    };
+    if libraries.values().all(Value::is_object) {
+        return libraries.clone();
+    }
+    let default_file = match source.files.as_slice() {
+        [file] => file.path.clone(),
+        _ => String::new(),
+    };
+    let mut grouped = Map::new();
+    for (name, address) in libraries {
+        let (file, name) = match name.rsplit_once(':') {
+            Some((file, name)) => (file.to_string(), name),
+            None => (default_file.clone(), name.as_str()),
+        };
+        let entry = grouped.entry(file).or_insert_with(|| json!({}));
+        entry[name] = address.clone();
+    }
+    grouped
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_standard_json() {
+        let mut source = ContractSource::new("0x0");
+        source.add_file("Token.sol", "contract Token {}");
+        source.metadata.compiler_version = Some("0.8.19+commit.7dd6d404".to_string());
+        source.metadata.optimization_enabled = Some(true);
+        source.metadata.optimization_runs = Some(1000);
+        source.metadata.evm_version = Some("paris".to_string());
+        source.metadata.libraries = Some(json!({"SafeMath": "0x01"}));
+        let input = build(&source).unwrap();
+        assert_eq!(input["language"], "Solidity");
+        assert_eq!(input["sources"]["Token.sol"]["content"], "contract Token {}");
+        assert_eq!(input["settings"]["optimizer"], json!({"enabled": true, "runs": 1000}));
+        assert_eq!(input["settings"]["evmVersion"], "paris");
+        assert_eq!(input["settings"]["libraries"], json!({"Token.sol": {"SafeMath": "0x01"}}));
+        assert!(input["settings"]["outputSelection"]["*"].is_object());
+
+        // Sourcify metadata.json 中的 settings
+        source.metadata.compiler_settings = Some(json!({
+            "compilationTarget": {"Token.sol": "Token"},
+            "libraries": {"lib/Math.sol:Math": "0x02"},
+            "optimizer": {"enabled": false, "runs": 200},
+            "remappings": ["@oz/=lib/oz/"]
+        }));
+        let input = build(&source).unwrap();
+        assert!(input["settings"].get("compilationTarget").is_none());
+        assert_eq!(input["settings"]["libraries"], json!({"lib/Math.sol": {"Math": "0x02"}}));
+        assert_eq!(input["settings"]["optimizer"]["enabled"], false);
+        assert_eq!(input["settings"]["remappings"][0], "@oz/=lib/oz/");
+
+        source.input = Some(json!({"language": "Solidity", "sources": {}, "settings": {"viaIR": true}}));
+        assert_eq!(build(&source).unwrap()["settings"]["viaIR"], true);
+
+        let mut vyper = ContractSource::new("0x0");
+        vyper.add_file("Vault.vy", "# @version 0.3.7");
+        vyper.metadata.compiler_version = Some("vyper:0.3.7".to_string());
+        assert_eq!(build(&vyper).unwrap()["language"], "Vyper");
+        assert!(build(&ContractSource::new("0x0")).is_none());
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+
+use crate::provider::ContractSource;
+use crate::rpc::{word_to_address, RpcClient};
+
+/// EIP-1967 实现合约地址的存储位置，`bytes32(uint256(keccak256('eip1967.proxy.implementation')) - 1)`
+const EIP1967_IMPLEMENTATION_SLOT: &str = "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+/// EIP-1967 beacon 地址的存储位置，`bytes32(uint256(keccak256('eip1967.proxy.beacon')) - 1)`
+const EIP1967_BEACON_SLOT: &str = "0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+/// EIP-1822 (UUPS) 的存储位置，`keccak256('PROXIABLE')`
+const EIP1822_SLOT: &str = "0xc5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7";
+/// beacon 的 `implementation()`
+const IMPLEMENTATION_SELECTOR: &str = "0x5c60da1b";
+
+
+/// 代理合约指向的实现合约
+#[derive(Debug, Clone, PartialEq)]
+pub struct Implementation {
+    pub address: String,
+    /// 识别方式：`explorer`、`eip1967`、`eip1822` 或 `beacon`
+    pub kind: &'static str,
+}
+
+
+/// 判断合约是否为代理合约，优先使用区块浏览器返回的实现合约地址，
+/// 其次通过 JSON-RPC 读取 EIP-1967、EIP-1822 和 beacon 的存储位置
+pub async fn detect(source: &ContractSource, rpc: Option<&RpcClient>) -> Result<Option<Implementation>> {
+    if let Some(address) = &source.metadata.implementation {
+        return Ok(Some(Implementation { address: address.clone(), kind: "explorer" }));
+    }
+    let Some(rpc) = rpc else {
+        return Ok(None);
+    };
+    let address = source.address.as_str();
+    for (slot, kind) in [(EIP1967_IMPLEMENTATION_SLOT, "eip1967"), (EIP1822_SLOT, "eip1822")] {
+        if let Some(implementation) = word_to_address(&rpc.get_storage_at(address, slot).await?) {
+            return Ok(Some(Implementation { address: implementation, kind }));
+        }
+    }
+    if let Some(beacon) = word_to_address(&rpc.get_storage_at(address, EIP1967_BEACON_SLOT).await?) {
+        let word = rpc.call(&beacon, IMPLEMENTATION_SELECTOR).await?;
+        if let Some(implementation) = word_to_address(&word) {
+            return Ok(Some(Implementation { address: implementation, kind: "beacon" }));
+        }
+    }
+    Ok(None)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+    use serde_json::json;
+
+    const ZERO: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+    const BEACON: &str = "0x000000000000000000000000000000000000000000000000000000000000beef";
+    const IMPLEMENTATION: &str = "0x00000000000000000000000043506849d7c04f9138d1a2050bbf3a0c054402dd";
+
+    fn storage_mock(slot: &str, value: &str) -> mockito::Mock {
+        mock("POST", "/proxy")
+            .match_body(Matcher::PartialJson(json!({"method": "eth_getStorageAt", "params": ["0x01", slot, "latest"]})))
+            .with_body(json!({"jsonrpc": "2.0", "id": 1, "result": value}).to_string())
+            .create()
+    }
+
+    #[tokio::test]
+    async fn test_detect_proxy() {
+        let mut source = ContractSource::new("0x01");
+        source.metadata.implementation = Some("0x02".to_string());
+        let found = detect(&source, None).await.unwrap().unwrap();
+        assert_eq!((found.address.as_str(), found.kind), ("0x02", "explorer"));
+
+        source.metadata.implementation = None;
+        assert_eq!(detect(&source, None).await.unwrap(), None);
+
+        // 通过 beacon 获取实现合约
+        let _implementation = storage_mock(EIP1967_IMPLEMENTATION_SLOT, ZERO);
+        let _uups = storage_mock(EIP1822_SLOT, ZERO);
+        let _beacon = storage_mock(EIP1967_BEACON_SLOT, BEACON);
+        let _call = mock("POST", "/proxy")
+            .match_body(Matcher::PartialJson(json!({
+                "method": "eth_call",
+                "params": [{"to": "0x000000000000000000000000000000000000beef", "data": IMPLEMENTATION_SELECTOR}]
+            })))
+            .with_body(json!({"jsonrpc": "2.0", "id": 1, "result": IMPLEMENTATION}).to_string())
+            .create();
+        let rpc = RpcClient::new(&format!("{}/proxy", mockito::server_url()));
+        let found = detect(&source, Some(&rpc)).await.unwrap().unwrap();
+        assert_eq!(found.address, "0x43506849d7c04f9138d1a2050bbf3a0c054402dd");
+        assert_eq!(found.kind, "beacon");
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::error::RetrieveError;
+
+/// 成功获取源码的状态名称
+pub const FETCHED: &str = "fetched";
+/// CSV 中无法解析的行
+pub const INVALID_ROW: &str = "invalid_row";
+
+
+/// 批量模式中一行的处理结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Outcome {
+    /// 在输入文件中的行号，从 1 开始
+    pub line: u64,
+    pub chain: String,
+    pub address: String,
+    /// `fetched`、`invalid_row` 或者 `RetrieveError::kind()`
+    pub status: String,
+    pub error: Option<String>,
+}
+
+impl Outcome {
+    pub fn new(line: u64, chain: &str, address: &str, result: &Result<(), RetrieveError>) -> Self {
+        let (status, error) = match result {
+            Ok(()) => (FETCHED, None),
+            Err(e) => (e.kind(), Some(e.to_string())),
+        };
+        Outcome {
+            line,
+            chain: chain.to_string(),
+            address: address.to_string(),
+            status: status.to_string(),
+            error,
+        }
+    }
+
+    pub fn invalid_row(line: u64, error: &str) -> Self {
+        Outcome {
+            line,
+            chain: String::new(),
+            address: String::new(),
+            status: INVALID_ROW.to_string(),
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+
+/// 批量任务的汇总报告
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub total: usize,
+    /// 各个状态的数量
+    pub counts: BTreeMap<String, usize>,
+    /// 所有没有成功获取源码的行，按行号排序
+    pub failures: Vec<Outcome>,
+}
+
+impl Report {
+    pub fn new(outcomes: impl IntoIterator<Item = Outcome>) -> Self {
+        let mut report = Report::default();
+        for outcome in outcomes {
+            report.total += 1;
+            *report.counts.entry(outcome.status.clone()).or_default() += 1;
+            if outcome.status != FETCHED {
+                report.failures.push(outcome);
+            }
+        }
+        report.failures.sort_by_key(|o| o.line);
+        report
+    }
+
+    pub fn print(&self) {
+        println!("Summary: {} rows", self.total);
+        for (status, count) in &self.counts {
+            println!("  {:<16} {}", status, count);
+        }
+        if !self.failures.is_empty() {
+            println!("Failed rows:");
+            for o in &self.failures {
+                println!("  line {:<6} {:<10} {:<44} {}: {}",
+                         o.line, o.chain, o.address, o.status, o.error.as_deref().unwrap_or(""));
+            }
+        }
+    }
+
+    /// 有失败的行时返回 `PartialFailure`，用于批量模式的退出码
+    pub fn result(&self) -> Result<(), RetrieveError> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        Err(RetrieveError::PartialFailure(format!("{} of {} rows failed", self.failures.len(), self.total)))
+    }
+
+    /// 根据扩展名保存为 JSON 或 CSV，CSV 中只包含失败的行
+    pub fn write(&self, path: &str) -> Result<()> {
+        let extension = Path::new(path).extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("json") => fs::write(path, serde_json::to_string_pretty(self)?)?,
+            Some("csv") => {
+                let mut wtr = csv::Writer::from_path(path)?;
+                for outcome in &self.failures {
+                    wtr.serialize(outcome)?;
+                }
+                wtr.flush()?;
+            }
+            _ => return Err(anyhow!("Unsupported report format '{}', expected .json or .csv", path)),
+        }
+        println!("Saved: {}", path);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let report = Report::new([
+            Outcome::new(3, "eth", "0x02", &Err(RetrieveError::RateLimited("Max rate limit reached".to_string()))),
+            Outcome::new(2, "eth", "0x01", &Ok(())),
+            Outcome::invalid_row(4, "missing chain"),
+            Outcome::new(5, "eth", "0x03", &Ok(())),
+        ]);
+        assert_eq!(report.total, 4);
+        assert_eq!(report.counts[FETCHED], 2);
+        assert_eq!(report.counts["rate_limited"], 1);
+        let lines: Vec<_> = report.failures.iter().map(|o| o.line).collect();
+        assert_eq!(lines, [3, 4]);
+        assert_eq!(report.result().unwrap_err().exit_code(), 13);
+        assert!(Report::new([Outcome::new(2, "eth", "0x01", &Ok(()))]).result().is_ok());
+
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("report.csv");
+        report.write(csv_path.to_str().unwrap()).unwrap();
+        let csv = fs::read_to_string(&csv_path).unwrap();
+        assert!(csv.starts_with("line,chain,address,status,error\n3,eth,0x02,rate_limited,"));
+        let json_path = dir.path().join("report.json");
+        report.write(json_path.to_str().unwrap()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+        assert_eq!(json["counts"]["invalid_row"], 1);
+        assert!(report.write(dir.path().join("report.txt").to_str().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use crate::error::RetrieveError;
+use crate::http::{host, Client};
+
+
+/// 以太坊 JSON-RPC 客户端，只实现需要用到的几个只读方法
+pub struct RpcClient {
+    url: String,
+    client: Client,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Self {
+        RpcClient {
+            url: url.to_string(),
+            client: Client::new(url, 10.0),
+        }
+    }
+
+    /// RPC 地址中通常带有节点服务的 key，错误信息中只保留域名，`Client` 返回的错误已经去掉了 URL
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let body = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+        let resp = self.client.post_json(&self.url, &body).await?
+            .ok_or_else(|| RetrieveError::Network(format!("JSON-RPC endpoint on {} not found", host(&self.url))))?;
+        if let Some(error) = resp.get("error") {
+            let message = error["message"].as_str().unwrap_or("unknown error");
+            return Err(RetrieveError::Api(format!("JSON-RPC {} failed: {}", method, message)).into());
+        }
+        Ok(resp["result"].clone())
+    }
+
+    /// 返回 32 字节的十六进制字符串
+    pub async fn get_storage_at(&self, address: &str, slot: &str) -> Result<String> {
+        let result = self.request("eth_getStorageAt", json!([address, slot, "latest"])).await?;
+        hex_result(result)
+    }
+
+    /// 合约的运行时字节码，不是合约的地址返回 `0x`
+    pub async fn get_code(&self, address: &str) -> Result<String> {
+        let result = self.request("eth_getCode", json!([address, "latest"])).await?;
+        hex_result(result)
+    }
+
+    /// 合约创建交易的 `input`，即创建字节码，交易不是直接创建合约时返回 `None`
+    pub async fn get_creation_input(&self, tx_hash: &str) -> Result<Option<String>> {
+        let tx = self.request("eth_getTransactionByHash", json!([tx_hash])).await?;
+        if !tx["to"].is_null() {
+            return Ok(None);
+        }
+        Ok(tx["input"].as_str().map(str::to_string))
+    }
+
+    /// 调用合约的只读方法，`data` 为 ABI 编码后的调用数据
+    pub async fn call(&self, to: &str, data: &str) -> Result<String> {
+        let result = self.request("eth_call", json!([{"to": to, "data": data}, "latest"])).await?;
+        hex_result(result)
+    }
+}
+
+
+fn hex_result(result: Value) -> Result<String> {
+    match result.as_str() {
+        Some(hex) if hex.starts_with("0x") => Ok(hex.to_string()),
+        _ => Err(RetrieveError::ParseError(format!("Invalid JSON-RPC result: {}", result)).into()),
+    }
+}
+
+
+/// 从 32 字节的存储值或返回值中取出地址，零地址返回 `None`
+pub fn word_to_address(word: &str) -> Option<String> {
+    let hex = word.strip_prefix("0x").unwrap_or(word);
+    // 先检查是否为十六进制，避免按字节切片时出错
+    if hex.len() < 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) || hex.bytes().all(|b| b == b'0') {
+        return None;
+    }
+    Some(format!("0x{}", &hex[hex.len() - 40..]))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, Matcher};
+
+    #[tokio::test]
+    async fn test_rpc_requests() {
+        let _m = mock("POST", "/rpc")
+            .match_body(Matcher::PartialJson(json!({"method": "eth_getStorageAt", "params": ["0x01", "0x02", "latest"]})))
+            .with_body(r#"{"jsonrpc": "2.0", "id": 1, "result": "0x00000000000000000000000043506849d7c04f9138d1a2050bbf3a0c054402dd"}"#)
+            .create();
+        let _error = mock("POST", "/rpc")
+            .match_body(Matcher::PartialJson(json!({"method": "eth_call"})))
+            .with_body(r#"{"jsonrpc": "2.0", "id": 1, "error": {"code": -32000, "message": "execution reverted"}}"#)
+            .create();
+        let rpc = RpcClient::new(&format!("{}/rpc", mockito::server_url()));
+        let word = rpc.get_storage_at("0x01", "0x02").await.unwrap();
+        assert_eq!(word_to_address(&word).as_deref(), Some("0x43506849d7c04f9138d1a2050bbf3a0c054402dd"));
+        assert_eq!(word_to_address(&format!("0x{}", "0".repeat(64))), None);
+        assert_eq!(word_to_address(&format!("0x{}é", "0".repeat(40))), None);
+        assert_eq!(word_to_address("0xnot a hex word, but forty bytes or longer"), None);
+        assert!(rpc.call("0x01", "0x5c60da1b").await.unwrap_err().to_string().contains("execution reverted"));
+
+        let _tx = mock("POST", "/rpc")
+            .match_body(Matcher::PartialJson(json!({"method": "eth_getTransactionByHash"})))
+            .with_body(r#"{"jsonrpc": "2.0", "id": 1, "result": {"hash": "0xabc", "to": null, "input": "0x6080"}}"#)
+            .create();
+        assert_eq!(rpc.get_creation_input("0xabc").await.unwrap().as_deref(), Some("0x6080"));
+
+        // 错误信息中不包含 RPC 地址中的 key
+        let _forbidden = mock("POST", "/rpc/SECRET").with_status(403).create();
+        let rpc = RpcClient::new(&format!("{}/rpc/SECRET", mockito::server_url()));
+        let error = format!("{:#}", rpc.get_code("0x01").await.unwrap_err());
+        assert!(error.contains("403") && !error.contains("SECRET"), "{}", error);
+    }
+}